@@ -0,0 +1,79 @@
+use anchor_lang::prelude::*;
+
+declare_id!("BmsroFHNuASmJ4Aq1Z9sLc6xiHTJkYuqoH9SMehV54iJ");
+
+// stands in for a randomness oracle on localnet: a request names the round that will consume it,
+// and its authority writes the randomness later, like an off-chain oracle would
+#[program]
+pub mod mock_oracle {
+    use super::*;
+
+    pub fn request (
+        ctx : Context<Request>,
+        _requester : Pubkey,
+    ) -> ProgramResult {
+        msg!("+ request randomness");
+
+        let request = &mut ctx.accounts.request;
+
+        request.requester = _requester;
+        request.randomness = [0; 32];
+        request.authority = *ctx.accounts.authority.key;
+
+        Ok(())
+    }
+
+    pub fn fulfil (
+        ctx : Context<Fulfil>,
+        _randomness : [u8; 32],
+    ) -> ProgramResult {
+        msg!("+ fulfil randomness");
+
+        let request = &mut ctx.accounts.request;
+
+        if request.randomness != [0; 32] || _randomness == [0; 32] {
+            return Err(OracleError::InvalidRandomness.into());
+        }
+
+        request.randomness = _randomness;
+
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+pub struct Request<'info>{
+    #[account(mut)]
+    authority : Signer<'info>,
+
+    #[account(init,
+        payer = authority,
+        space = 8 + RANDOMNESS_REQUEST_SIZE)]
+    request : ProgramAccount<'info, RandomnessRequest>,
+
+    system_program : Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct Fulfil<'info>{
+    authority : Signer<'info>,
+
+    #[account(mut,
+        has_one = authority)]
+    request : ProgramAccount<'info, RandomnessRequest>,
+}
+
+// requester and randomness lead the account, where the lottery's get_oracle_randomness reads them
+pub const RANDOMNESS_REQUEST_SIZE : usize = 32 + 32 + 32;
+#[account]
+pub struct RandomnessRequest {
+    pub requester : Pubkey,
+    pub randomness : [u8; 32],
+    pub authority : Pubkey,
+}
+
+#[error]
+pub enum OracleError {
+    #[msg("Randomness is already set or empty")]
+    InvalidRandomness,
+}
@@ -1,10 +1,18 @@
 use anchor_lang::{
     prelude::*,
+    solana_program::{
+        hash::hashv,
+        program_pack::Pack,
+        sysvar::slot_hashes,
+    },
     Discriminator,
 };
-use anchor_spl::token::{self, Token, TokenAccount, Mint, Transfer};
+use arrayref::array_ref;
+use anchor_spl::token::{Token, TokenAccount, Mint};
 use crate::utils::*;
 
+pub mod utils;
+
 declare_id!("Fg6PaFpoGXkYsidMpWTK6W2BeZ7FEfcYkg476zPFsLnS");
 
 #[program]
@@ -15,451 +23,3367 @@ pub mod solana_anchor {
         ctx : Context<InitPool>,
         _bump : u8,
         _fee : u64,
+        _crank_reward : u64,
         ) -> ProgramResult {
         msg!("+ init_pool");
 
+        if _fee > FEE_DENOMINATOR {
+            return Err(PoolError::InvalidFee.into());
+        }
+
+        if _crank_reward > MAX_CRANK_REWARD {
+            return Err(PoolError::InvalidCrankReward.into());
+        }
+
         let pool = &mut ctx.accounts.pool;
 
         pool.owner = *ctx.accounts.owner.key;
+        pool.pending_owner = Pubkey::default();
         pool.rand = *ctx.accounts.rand.key;
         pool.fee_receiver = ctx.accounts.fee_receiver.key();
-        pool.winner = ctx.accounts.winner.key();
+        pool.fee_recipients = vec![FeeRecipient {
+            recipient : ctx.accounts.fee_receiver.key(),
+            share : FEE_DENOMINATOR as u16,
+        }];
         pool.fee = _fee;
+        pool.referral_share = 0;
+        pool.crank_reward = _crank_reward;
+        pool.pending_fees = 0;
+        pool.total_fees = 0;
+        pool.collected_fees = 0;
+        pool.admins = vec![];
+        pool.threshold = 0;
+        pool.proposal_count = 0;
         pool.bump = _bump;
 
         Ok(())
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn start_round (
         ctx: Context<StartRound>,
+        _bump : u8,
         _round_name : String,
         _total_ticket : u64,
         _round_period : u64,
+        _ticket_price : u64,
+        _commitment : [u8; 32],
+        _tiers : Vec<PrizeTier>,
+        _ledger_bump : u8,
+        _max_tickets_per_wallet : u32,
+        _min_tickets : u32,
+        _vault_bump : u8,
     ) -> ProgramResult {
         msg!("+ start new round");
 
+        // the approval covers the round's terms, not just its address
+        let tiers_data = _tiers.try_to_vec()?;
+        let round_key = get_args_key(&[
+            ctx.accounts.round_data.key().as_ref(),
+            _round_name.as_bytes(),
+            &_total_ticket.to_le_bytes(),
+            &_round_period.to_le_bytes(),
+            &_ticket_price.to_le_bytes(),
+            &_commitment,
+            &tiers_data,
+            &_max_tickets_per_wallet.to_le_bytes(),
+            &_min_tickets.to_le_bytes(),
+        ]);
+        consume_admin_approval(
+            &ctx.accounts.pool,
+            &ctx.accounts.admin_proposal,
+            AdminAction::StartRound,
+            &round_key,
+            0,
+        )?;
+
+        check_prize_tiers(&_tiers)?;
+
+        if _total_ticket == 0 || _total_ticket > u32::MAX as u64 {
+            return Err(PoolError::InvalidTicketCount.into());
+        }
+
+        if _min_tickets as u64 > _total_ticket {
+            return Err(PoolError::InvalidTicketCount.into());
+        }
+
+        if _ticket_price == 0 {
+            return Err(PoolError::InvalidTicketPrice.into());
+        }
+
+        if !(MIN_ROUND_PERIOD..=MAX_ROUND_PERIOD).contains(&_round_period) {
+            return Err(PoolError::InvalidPeriod.into());
+        }
+
         let round_data = &mut ctx.accounts.round_data;
         let clock = Clock::from_account_info(&ctx.accounts.clock)?;
 
         round_data.pool = ctx.accounts.pool.key();
-        round_data.ticket_ledger = *ctx.accounts.ticket_ledger.key;
+        round_data.last_number = 0;
+        round_data.ledger_page = 0;
+        round_data.page_entries = 0;
+        round_data.last_buyer = Pubkey::default();
         round_data.total_ticket = _total_ticket;
+        round_data.max_tickets_per_wallet = _max_tickets_per_wallet;
+        round_data.min_tickets = _min_tickets;
+        round_data.ticket_price = _ticket_price;
         round_data.start_time = clock.unix_timestamp as u64;
         round_data.round_period = _round_period;
         round_data.tvl = 0;
+        round_data.ticket_sales = 0;
+        round_data.fees_collected = 0;
+        round_data.refunded_amount = 0;
+        round_data.fees_claimed = 0;
         round_data.claimed = false;
         round_data.finished = false;
+        round_data.cancelled = false;
+        round_data.refund_fees = false;
         round_data.round_name = _round_name;
+        round_data.mint = Pubkey::default();
+        round_data.vault = ctx.accounts.vault.key();
+        round_data.commitment = _commitment;
+        round_data.reveal = [0; 32];
+        round_data.revealed = false;
+        round_data.draw_slot = 0;
+        round_data.oracle = Pubkey::default();
+        round_data.tiers = _tiers;
+        round_data.winners = vec![];
         round_data.bump = _bump;
 
-        let mut data = (&mut ctx.accounts.ticket_ledger).data.borrow_mut();
-        let mut new_data = TicketList::discriminator().try_to_vec().unwrap();
+        create_ledger_page(
+            ctx.accounts.owner.to_account_info(),
+            ctx.accounts.ticket_ledger.clone(),
+            ctx.accounts.system_program.to_account_info(),
+            &ctx.accounts.rent,
+            &round_data.key(),
+            0,
+            0,
+            _ledger_bump,
+        )?;
 
-        new_data.append(&mut round_data.key().try_to_vec().unwrap());
-        new_data.append(&mut (0 as u32).try_to_vec().unwrap());
+        create_round_escrow(
+            ctx.accounts.owner.to_account_info(),
+            ctx.accounts.vault.clone(),
+            ctx.accounts.system_program.to_account_info(),
+            &ctx.accounts.rent,
+            &round_data.key(),
+            _vault_bump,
+        )
+    }
 
-        for i in 0..bew_data.len() {
-            data[i] = new_data[i];
-        }
+    pub fn init_round_vault (
+        ctx : Context<InitRoundVault>,
+        _bump : u8,
+    ) -> ProgramResult {
+        msg!("+ init round vault");
+
+        let vault_key = get_args_key(&[
+            ctx.accounts.round.key().as_ref(),
+            ctx.accounts.mint.key().as_ref(),
+        ]);
+        consume_admin_approval(
+            &ctx.accounts.pool,
+            &ctx.accounts.admin_proposal,
+            AdminAction::InitRoundVault,
+            &vault_key,
+            0,
+        )?;
+
+        let round = &mut ctx.accounts.round;
 
-        let vec_start = 8 + 32 + 4;
-        let as_bytes = (_total_ticket as u32).to_le_bytes();
+        // the vault can only be switched before anything has been paid into or settled on the round
+        if round.mint != Pubkey::default()
+            || round.tvl != 0
+            || round.last_number != 0
+            || round.fees_collected != 0
+            || round.cancelled
+            || round.finished
+        {
+            return Err(PoolError::InvalidRoundVault.into());
+        }
 
-        for i in 0..4 {
-            data[vec_start + i] = as_bytes[i];
+        if !cmp_pubkeys(ctx.accounts.escrow.key, &round.vault) {
+            return Err(PoolError::InvalidRoundVault.into());
         }
 
+        // a token round never uses the lamport escrow made by start_round, so it is closed back to the owner
+        let escrow_lamports = ctx.accounts.escrow.lamports();
+        sol_transfer(
+            SolTransferParams {
+                source: ctx.accounts.escrow.clone(),
+                destination: ctx.accounts.owner.to_account_info(),
+                amount: escrow_lamports,
+            }
+        )?;
+
+        round.mint = ctx.accounts.mint.key();
+        round.vault = ctx.accounts.vault.key();
+
         Ok(())
     }
 
-    pub fn finish_round (
-        ctx: Context<FinishRound>
+    pub fn set_oracle_program (
+        ctx : Context<SetOracleProgram>,
     ) -> ProgramResult {
-        msg!("+ finish current round");
+        msg!("+ set oracle program");
+
+        consume_admin_approval(
+            &ctx.accounts.pool,
+            &ctx.accounts.admin_proposal,
+            AdminAction::SetOracleProgram,
+            &ctx.accounts.oracle_program.key(),
+            0,
+        )?;
 
-        let round = &mut ctx.accounts.round;
+        let pool = &mut ctx.accounts.pool;
 
-        // Generate a random number
-        let recent_slothashes = &ctx.accounts.recent_blockhashes;
-        if cmp_pubkeys(&recent_slothashes.key(), &BLOCK_HASHES) {
-            msg!("recent_blockhashes is deprecated and will break soon");
+        pool.oracle_program = ctx.accounts.oracle_program.key();
+
+        Ok(())
+    }
+
+    pub fn update_pool_config (
+        ctx : Context<UpdatePoolConfig>,
+        _fee : u64,
+        _crank_reward : u64,
+        _recipients : Vec<FeeRecipient>,
+    ) -> ProgramResult {
+        msg!("+ update pool config");
+
+        if _fee > FEE_DENOMINATOR {
+            return Err(PoolError::InvalidFee.into());
         }
-        if !cmp_pubkeys(&recent_slothashes.key(), &SlotHashes::id())
-            && !cmp_pubkeys(&recent_slothashes.key(), &BLOCK_HASHES)
-        {
-            return err!(PoolError::IncorrectSlotHashesPubkey);
+
+        if _crank_reward > MAX_CRANK_REWARD {
+            return Err(PoolError::InvalidCrankReward.into());
         }
 
-        let data = recent_slothashes.data.borrow();
-        let most_recent = array_ref![data, 12, 8];
+        check_fee_recipients(&_recipients)?;
+
+        let recipients_data = _recipients.try_to_vec()?;
+        let config_key = get_args_key(&[
+            &_fee.to_le_bytes(),
+            &_crank_reward.to_le_bytes(),
+            ctx.accounts.fee_receiver.key.as_ref(),
+            &recipients_data,
+        ]);
+        consume_admin_approval(
+            &ctx.accounts.pool,
+            &ctx.accounts.admin_proposal,
+            AdminAction::UpdatePoolConfig,
+            &config_key,
+            0,
+        )?;
 
-        let winner_index = u64::from_le_bytes(*most_recent);
-        /////////////////////////////
+        let pool = &mut ctx.accounts.pool;
 
-        let winner_ticket = get_winning_ticket(&ctx.accounts.ticket_ledger, winner_index as usize)?;
+        // the receiver only changes together with the schedule it sweeps into
+        pool.fee = _fee;
+        pool.crank_reward = _crank_reward;
+        pool.fee_receiver = ctx.accounts.fee_receiver.key();
+        pool.fee_recipients = _recipients;
 
-        round.winner = winner_ticket.owner;
-        round.finished = true;
+        emit!(PoolConfigUpdated {
+            pool : pool.key(),
+            fee : _fee,
+            crank_reward : _crank_reward,
+            fee_receiver : pool.fee_receiver,
+            recipients : pool.fee_recipients.clone(),
+        });
 
         Ok(())
     }
 
-    pub buy_ticket (
-        ctx : Context<BuyTicket>,
+    pub fn propose_owner (
+        ctx : Context<ProposeOwner>,
+        _new_owner : Pubkey,
     ) -> ProgramResult {
-        msg!("+ buy ticket");
+        msg!("+ propose owner");
 
         let pool = &mut ctx.accounts.pool;
-        let round = &mut ctx.accounts.round;
 
-        let last_number = get_last_number(&ctx.accounts.ticket_ledger)?;
+        // Pubkey::default() withdraws a pending proposal
+        pool.pending_owner = _new_owner;
 
-        if last_number > round.total_ticket - 1 {
-            return Err(PoolError::TicketLimitReached.into());
-        }
+        emit!(OwnerProposed {
+            pool : pool.key(),
+            owner : pool.owner,
+            pending_owner : _new_owner,
+        });
 
-        sol_transfer_without_seed(
-            SolTransferParamsWithoutSeed {
-                source: ctx.accounts.owner.clone(),
-                destination: pool.to_account_info().clone(),
-                system_program: ctx.accounts.system_program.to_account_info().clone(),
-                amount: 0.25 * 1_000_000_000,
-            }
-        )?;
+        Ok(())
+    }
 
-        sol_transfer_without_seed(
-            SolTransferParamsWithoutSeed {
-                source: ctx.accounts.owner.clone(),
-                destination: ctx.accounts.fee_receiver.clone(),
-                system_program: ctx.accounts.system_program.to_account_info().clone(),
-                amount: 0.02 * 1_000_000_000,
-            }
-        )?;
+    pub fn accept_owner (
+        ctx : Context<AcceptOwner>,
+    ) -> ProgramResult {
+        msg!("+ accept owner");
 
-        set_ticket_owner(
-            &mut ctx.accounts.ticket_ledger, 
-            last_number as usize, 
-            TicketData {
-                ticket_index : (last_number + 1) as u64,
-                owner : *ctx.accounts.owner.key
-            }
-        );
+        let pool = &mut ctx.accounts.pool;
 
-        set_last_number(&mut ctx.accounts.ticket_ledger, last_number + 1);
+        let previous_owner = pool.owner;
+        pool.owner = pool.pending_owner;
+        pool.pending_owner = Pubkey::default();
 
-        round.tvl += 0.25 * 1_000_000_000;
+        emit!(OwnerChanged {
+            pool : pool.key(),
+            previous_owner,
+            owner : pool.owner,
+        });
 
         Ok(())
     }
 
-    pub fn claim {
-        ctx : Context<Claim>
-    } -> ProgramResult {
-        msg!("+ claim");
+    pub fn set_admins (
+        ctx : Context<SetAdmins>,
+        _admins : Vec<Pubkey>,
+        _threshold : u8,
+    ) -> ProgramResult {
+        msg!("+ set admins");
+
+        check_admins(&_admins, _threshold)?;
+
+        // the owner bootstraps the admin set, changing it afterwards needs the current admins
+        let admins_key = get_admins_key(&_admins, _threshold);
+        consume_admin_approval(
+            &ctx.accounts.pool,
+            &ctx.accounts.admin_proposal,
+            AdminAction::SetAdmins,
+            &admins_key,
+            0,
+        )?;
 
         let pool = &mut ctx.accounts.pool;
-        let round = &mut ctx.accounts.round;
 
-        if !round.finished {
-            return Err(PoolError::RoundNotFinished.into());
+        pool.admins = _admins;
+        pool.threshold = _threshold;
+
+        Ok(())
+    }
+
+    pub fn propose_admin_action (
+        ctx : Context<ProposeAdminAction>,
+        _bump : u8,
+        _action : AdminAction,
+        _target : Pubkey,
+        _amount : u64,
+    ) -> ProgramResult {
+        msg!("+ propose admin action");
+
+        let pool = &mut ctx.accounts.pool;
+
+        if !pool.admins.contains(ctx.accounts.admin.key) {
+            return Err(PoolError::InvalidAdmin.into());
+        }
+
+        let proposal = &mut ctx.accounts.proposal;
+
+        proposal.pool = pool.key();
+        proposal.index = pool.proposal_count;
+        proposal.action = _action;
+        proposal.target = _target;
+        proposal.amount = _amount;
+        proposal.approvals = vec![*ctx.accounts.admin.key];
+        proposal.executed = false;
+        proposal.bump = _bump;
+
+        pool.proposal_count += 1;
+
+        Ok(())
+    }
+
+    pub fn approve_admin_action (
+        ctx : Context<ApproveAdminAction>,
+    ) -> ProgramResult {
+        msg!("+ approve admin action");
+
+        let pool = &ctx.accounts.pool;
+        let proposal = &mut ctx.accounts.proposal;
+
+        if !pool.admins.contains(ctx.accounts.admin.key) {
+            return Err(PoolError::InvalidAdmin.into());
         }
 
-        if *ctx.accounts.owner.key != round.winner && *ctx.accounts.owner.key != pool.winner {
-            return Err(PoolError::InvalidWInner.into());
+        if proposal.executed || proposal.approvals.contains(ctx.accounts.admin.key) {
+            return Err(PoolError::InvalidAdminProposal.into());
         }
 
-        sol_transfer(
-            SolTransferParams {
-                source: pool.to_account_info().clone(),
-                destination: ctx.accounts.owner.clone(),
-                amount: round.tvl,
-            }
+        proposal.approvals.push(*ctx.accounts.admin.key);
+
+        Ok(())
+    }
+
+    pub fn set_fee_recipients (
+        ctx : Context<SetFeeRecipients>,
+        _recipients : Vec<FeeRecipient>,
+    ) -> ProgramResult {
+        msg!("+ set fee recipients");
+
+        check_fee_recipients(&_recipients)?;
+
+        let recipients_data = _recipients.try_to_vec()?;
+        consume_admin_approval(
+            &ctx.accounts.pool,
+            &ctx.accounts.admin_proposal,
+            AdminAction::SetFeeRecipients,
+            &get_args_key(&[&recipients_data]),
+            0,
         )?;
 
-        round.claimed = true;
-        round.tvl = 0;
+        let pool = &mut ctx.accounts.pool;
+
+        pool.fee_recipients = _recipients;
+
+        emit!(FeeRecipientsUpdated {
+            pool : pool.key(),
+            recipients : pool.fee_recipients.clone(),
+        });
 
         Ok(())
     }
 
-    pub fn withdraw {
-        ctx : Context<WithDraw>,
-        _amount : u64
-    } -> ProgramResult {
-        msg!("+ withdraw");
+    pub fn set_referral_share (
+        ctx : Context<SetReferralShare>,
+        _referral_share : u64,
+    ) -> ProgramResult {
+        msg!("+ set referral share");
+
+        if _referral_share > FEE_DENOMINATOR {
+            return Err(PoolError::InvalidFee.into());
+        }
+
+        consume_admin_approval(
+            &ctx.accounts.pool,
+            &ctx.accounts.admin_proposal,
+            AdminAction::SetReferralShare,
+            &ctx.accounts.pool.key(),
+            _referral_share,
+        )?;
 
         let pool = &mut ctx.accounts.pool;
-        let round = &mut ctx.accounts.round;
 
+        pool.referral_share = _referral_share;
+
+        emit!(ReferralShareUpdated {
+            pool : pool.key(),
+            referral_share : _referral_share,
+        });
+
+        Ok(())
+    }
+
+    pub fn register_referrer (
+        ctx : Context<RegisterReferrer>,
+        _bump : u8,
+    ) -> ProgramResult {
+        msg!("+ register referrer");
+
+        let referrer = &mut ctx.accounts.referrer;
+
+        referrer.pool = ctx.accounts.pool.key();
+        referrer.owner = *ctx.accounts.owner.key;
+        referrer.referred_volume = 0;
+        referrer.earned = 0;
+        referrer.claimed = 0;
+        referrer.bump = _bump;
+
+        Ok(())
+    }
+
+    pub fn claim_referral_rewards (
+        ctx : Context<ClaimReferralRewards>,
+    ) -> ProgramResult {
+        msg!("+ claim referral rewards");
+
+        let referrer = &mut ctx.accounts.referrer;
+
+        let amount = referrer.earned - referrer.claimed;
+        if amount == 0 {
+            return Err(PoolError::NoFeesToCollect.into());
+        }
+
+        // commissions are paid into the referrer PDA itself
         sol_transfer(
             SolTransferParams {
-                source: pool.to_account_info().clone(),
-                destination: ctx.accounts.owner.clone(),
-                amount: _amount,
+                source: referrer.to_account_info(),
+                destination: ctx.accounts.owner.to_account_info(),
+                amount,
             }
         )?;
-        
-        round.tvl -= _amount;
+
+        referrer.claimed += amount;
 
         Ok(())
     }
 
-    pub fn deposit {
-        ctx : Context<Deposit>,
-        _amount : u64
-    } -> ProgramResult {
-        msg!("+ deposit");
+    // opens the referrer's commission account for a mint, token rounds of that mint pay into it
+    pub fn init_commission_account (
+        _ctx : Context<InitCommissionAccount>,
+        _bump : u8,
+    ) -> ProgramResult {
+        msg!("+ init commission account");
 
-        let pool = &mut ctx.accounts.pool;
-        let round = &mut ctx.accounts.round;
+        Ok(())
+    }
+
+    pub fn claim_referral_token_rewards (
+        ctx : Context<ClaimReferralTokenRewards>,
+        _bump : u8,
+    ) -> ProgramResult {
+        msg!("+ claim referral token rewards");
+
+        let pool = &ctx.accounts.pool;
+
+        let amount = ctx.accounts.commission.amount;
+        if amount == 0 {
+            return Err(PoolError::NoFeesToCollect.into());
+        }
 
-        sol_transfer_without_seed(
-            SolTransferParamsWithoutSeed {
-                source: ctx.accounts.owner.clone(),
-                destination: pool.to_account_info().clone(),
-                system_program: ctx.accounts.system_program.to_account_info().clone(),
-                amount: _amount,
+        let pool_seeds : &[&[u8]] = &[pool.rand.as_ref(), &[pool.bump]];
+        spl_token_transfer(
+            TokenTransferParams {
+                source: ctx.accounts.commission.to_account_info(),
+                destination: ctx.accounts.token_account.to_account_info(),
+                authority: pool.to_account_info(),
+                authority_signer_seeds: pool_seeds,
+                token_program: ctx.accounts.token_program.to_account_info(),
+                amount,
             }
         )?;
-        
-        round.tvl += _amount;
 
         Ok(())
     }
-}
 
+    pub fn request_randomness (
+        ctx : Context<RequestRandomness>,
+    ) -> ProgramResult {
+        msg!("+ request randomness");
+
+        let oracle_key = get_args_key(&[
+            ctx.accounts.round.key().as_ref(),
+            ctx.accounts.oracle.key.as_ref(),
+        ]);
+        consume_admin_approval(
+            &ctx.accounts.pool,
+            &ctx.accounts.admin_proposal,
+            AdminAction::RequestRandomness,
+            &oracle_key,
+            0,
+        )?;
 
-#[derive(Accounts)]
-#[instruction(_bump : u8)]
-pub struct InitPool<'info>{
-    #[account(mut)]
-    owner : Signer<'info>,
+        let pool = &ctx.accounts.pool;
+        let round = &mut ctx.accounts.round;
 
-    #[account(init,
-        seeds = [(*rand.key).as_ref()], 
-        bump = _bump, 
-        payer = owner, 
-        space = 8 + POOL_SIZE)]
-    pool : ProgramAccount<'info, Pool>,
+        if round.finished || round.oracle != Pubkey::default() {
+            return Err(PoolError::RandomnessAlreadyRequested.into());
+        }
 
-    rand : AccountInfo<'info>,
+        // a revealed round draws from its draw slot, switching to the oracle would let the owner redraw
+        if round.revealed {
+            return Err(PoolError::AlreadyRevealed.into());
+        }
 
-    fee_receiver : AccountInfo<'info>,
+        let clock = Clock::from_account_info(&ctx.accounts.clock)?;
+        if !is_sales_closed(round, clock.unix_timestamp as u64) {
+            return Err(PoolError::InvalidTime.into());
+        }
 
-    winner : AccountInfo<'info>,
+        if pool.oracle_program == Pubkey::default() {
+            return Err(PoolError::InvalidOracle.into());
+        }
 
-    system_program : Program<'info, System>,
-}
+        // the ticket set is final by now, so only a request made for this round and not yet
+        // answered may be bound, never one whose outcome is already known
+        let request = load_oracle_randomness(&ctx.accounts.oracle, &pool.oracle_program)?;
+        if !cmp_pubkeys(&request.requester, &round.key()) || request.randomness != [0; 32] {
+            return Err(PoolError::InvalidOracle.into());
+        }
 
-#[derive(Accounts)]
-#[instruction(_bump : u8, _round_name: String)]
-pub struct StartRound<'info>{
-    #[account(mut)]
-    owner : Signer<'info>,
+        round.oracle = ctx.accounts.oracle.key();
 
-    #[account(mut,
-        has_one = owner)]
-    pool : ProgramAccount<'info, Pool>,
+        Ok(())
+    }
 
-    #[account(mut)]
-    ticket_ledger : AccountInfo<'info>,
+    pub fn consume_randomness (
+        ctx : Context<ConsumeRandomness>,
+    ) -> ProgramResult {
+        msg!("+ consume randomness");
 
-    #[account(init, 
-        seeds = [pool.key().as_ref(), _round_name.as_ref()], 
-        bump = _bump, 
-        payer = owner, 
-        space = 8 + ROUND_SIZE)]
-    round_data : ProgramAccount<'info, Round>,
+        let pool = &ctx.accounts.pool;
+        let round = &mut ctx.accounts.round;
 
-    clock : AccountInfo<'info>,
+        if round.finished {
+            return Err(PoolError::RoundAlreadyFinished.into());
+        }
 
-    system_program : Program<'info, System>,
-}
+        let round_key = round.key();
+        let randomness = get_oracle_randomness(
+            &ctx.accounts.oracle,
+            &pool.oracle_program,
+            &round_key,
+        )?;
 
-#[derive(Accounts)]
-pub struct FinishRound<'info>{
-    #[account(mut)]
-    owner : Signer<'info>,
+        // the ledger pages holding the drawn tickets are passed as remaining accounts
+        draw_winners(round, &round_key, ctx.remaining_accounts, &randomness)
+    }
 
-    #[account(mut,
-        has_one = owner)]
-    pool : ProgramAccount<'info, Pool>,
+    pub fn reveal_round (
+        ctx : Context<RevealRound>,
+        _reveal : [u8; 32],
+    ) -> ProgramResult {
+        msg!("+ reveal round seed");
 
-    #[account(mut)]
-    ticket_ledger : AccountInfo<'info>,
+        let pool = &ctx.accounts.pool;
+        let round = &mut ctx.accounts.round;
 
-    #[account(mut,
-        has_one = pool,
-        has_one = ticket_ledger,
-        seeds = [round.pool.key().as_ref(), round.round_name.as_ref()], 
-        bump = round.bump)]
-    round : ProgramAccount<'info, Round>,
+        if round.revealed {
+            return Err(PoolError::AlreadyRevealed.into());
+        }
 
-    /// CHECK: checked in program.
-    recent_blockhashes: UncheckedAccount<'info>,
-}
+        // a round draws either from the reveal or from the oracle, never both
+        if round.oracle != Pubkey::default() {
+            return Err(PoolError::RandomnessAlreadyRequested.into());
+        }
 
-#[derive(Accounts)]
-pub struct BuyTicket<'info> {
-    #[account(mut)]
-    owner : Signer<'info>,
+        let clock = Clock::from_account_info(&ctx.accounts.clock)?;
+        if !is_sales_closed(round, clock.unix_timestamp as u64) {
+            return Err(PoolError::InvalidTime.into());
+        }
 
-    #[account(mut,
-        has_one = fee_receiver)]
-    pool : ProgramAccount<'info, Pool>,
+        if get_commitment(&pool.rand, &round.key(), &_reveal) != round.commitment {
+            return Err(PoolError::RevealMismatch.into());
+        }
 
-    #[account(mut)]
-    fee_receiver : AccountInfo<'info>,
+        round.reveal = _reveal;
+        round.revealed = true;
+        // the draw uses the hash of a slot that does not exist yet, so nobody knows it when the reveal goes public
+        round.draw_slot = clock.slot + DRAW_SLOT_DELAY;
 
-    #[account(mut,
-        has_one = pool,
-        has_one = ticket_ledger,
-        seeds = [round.pool.key().as_ref(), round.round_name.as_ref()], 
-        bump = round.bump)]
-    round : ProgramAccount<'info, Round>,
+        Ok(())
+    }
 
-    #[account(mut)]
-    ticket_ledger : AccountInfo<'info>,
+    pub fn finish_round (
+        ctx: Context<FinishRound>
+    ) -> ProgramResult {
+        msg!("+ finish current round");
 
-    system_program : Program<'info, System>,
-}
+        let round = &mut ctx.accounts.round;
 
-#[derive(Accounts)]
-pub struct Claim<'info> {
-    #[account(mut)]
-    owner : Signer<'info>,
+        if round.finished {
+            return Err(PoolError::RoundAlreadyFinished.into());
+        }
 
-    #[account(mut)]
+        if !round.revealed {
+            return Err(PoolError::RevealMissing.into());
+        }
+
+        if round.oracle != Pubkey::default() {
+            return Err(PoolError::RandomnessAlreadyRequested.into());
+        }
+
+        let clock = Clock::from_account_info(&ctx.accounts.clock)?;
+        if !is_sales_closed(round, clock.unix_timestamp as u64) {
+            return Err(PoolError::InvalidTime.into());
+        }
+
+        let draw_slot_hash = get_draw_slot_hash(round, &ctx.accounts.slot_hashes, &clock)?;
+
+        let seed = get_draw_seed(round, &draw_slot_hash);
+
+        // the approval is spent only by the call that actually draws
+        let round_key = round.key();
+        consume_admin_approval(
+            &ctx.accounts.pool,
+            &ctx.accounts.admin_proposal,
+            AdminAction::FinishRound,
+            &round_key,
+            0,
+        )?;
+
+        draw_winners(round, &round_key, ctx.remaining_accounts, &seed)
+    }
+
+    pub fn crank_finish_round<'info> (
+        ctx : Context<'_, '_, '_, 'info, CrankFinishRound<'info>>
+    ) -> ProgramResult {
+        msg!("+ crank finish round");
+
+        let pool = &ctx.accounts.pool;
+        let round = &mut ctx.accounts.round;
+
+        if round.finished {
+            return Err(PoolError::RoundAlreadyFinished.into());
+        }
+
+        if round.oracle != Pubkey::default() {
+            return Err(PoolError::RandomnessAlreadyRequested.into());
+        }
+
+        // without a reveal there is nothing unpredictable to draw from, such rounds end in cancel_round
+        if !round.revealed {
+            return Err(PoolError::RevealMissing.into());
+        }
+
+        // a sold-out round is revealed before its end time, the crank must be able to draw it
+        // while the draw slot is still in SlotHashes
+        let clock = Clock::from_account_info(&ctx.accounts.clock)?;
+        if !is_sales_closed(round, clock.unix_timestamp as u64) {
+            return Err(PoolError::InvalidTime.into());
+        }
+
+        let draw_slot_hash = get_draw_slot_hash(round, &ctx.accounts.slot_hashes, &clock)?;
+
+        // the reward comes off the top so prizes are shared from what remains
+        let reward = get_bps_amount(round.tvl, pool.crank_reward);
+        if reward > 0 {
+            if round.mint == Pubkey::default() {
+                sol_transfer(
+                    SolTransferParams {
+                        source: ctx.accounts.vault.clone(),
+                        destination: ctx.accounts.caller.to_account_info(),
+                        amount: reward,
+                    }
+                )?;
+            } else {
+                let token_accounts = get_round_token_accounts(round, ctx.remaining_accounts)?;
+                let pool_seeds : &[&[u8]] = &[pool.rand.as_ref(), &[pool.bump]];
+
+                spl_token_transfer(
+                    TokenTransferParams {
+                        source: token_accounts.vault.clone(),
+                        destination: token_accounts.token_account.clone(),
+                        authority: pool.to_account_info(),
+                        authority_signer_seeds: pool_seeds,
+                        token_program: token_accounts.token_program.clone(),
+                        amount: reward,
+                    }
+                )?;
+            }
+
+            round.tvl -= reward;
+        }
+
+        let seed = get_draw_seed(round, &draw_slot_hash);
+
+        // token rounds lead with [caller token, vault, token program], the ledger pages follow
+        let pages = if round.mint == Pubkey::default() {
+            ctx.remaining_accounts
+        } else {
+            ctx.remaining_accounts.get(3..).unwrap_or(&[])
+        };
+
+        let round_key = round.key();
+        draw_winners(round, &round_key, pages, &seed)
+    }
+
+    pub fn buy_ticket<'info> (
+        ctx : Context<'_, '_, '_, 'info, BuyTicket<'info>>,
+        _quantity : u32,
+        _ledger_bump : u8,
+        _player_bump : u8,
+        _ranges_bump : u8,
+    ) -> ProgramResult {
+        msg!("+ buy ticket");
+
+        let pool = &mut ctx.accounts.pool;
+        let round = &mut ctx.accounts.round;
+
+        let clock = Clock::from_account_info(&ctx.accounts.clock)?;
+
+        if round.revealed
+            || round.cancelled
+            || round.oracle != Pubkey::default()
+            || clock.unix_timestamp as u64 >= get_round_end_time(round)
+        {
+            return Err(PoolError::SalesClosed.into());
+        }
+
+        if _quantity == 0 {
+            return Err(PoolError::InvalidTicketCount.into());
+        }
+
+        let last_number = round.last_number;
+
+        if last_number as u64 + _quantity as u64 > round.total_ticket {
+            return Err(PoolError::TicketLimitReached.into());
+        }
+
+        // the player's entry is created on their first purchase in this round
+        let mut player_entry = if ctx.accounts.player_entry.data_is_empty() {
+            create_player_entry(
+                ctx.accounts.owner.to_account_info(),
+                ctx.accounts.player_entry.clone(),
+                ctx.accounts.system_program.to_account_info(),
+                &ctx.accounts.rent,
+                &round.key(),
+                ctx.accounts.owner.key,
+                _player_bump,
+            )?
+        } else {
+            load_player_entry(&ctx.accounts.player_entry, &round.key(), ctx.accounts.owner.key)?
+        };
+
+        if round.max_tickets_per_wallet != 0
+            && player_entry.tickets as u64 + _quantity as u64 > round.max_tickets_per_wallet as u64
+        {
+            return Err(PoolError::WalletTicketLimitReached.into());
+        }
+
+        // a repeat purchase by the last buyer extends their run, otherwise a new entry is appended;
+        // an append that finds the current page full opens the next one, page 0 is created by start_round
+        let mut page_index = round.ledger_page;
+        let mut page_offset = round.page_entries;
+        let mut extend_last = false;
+        if page_offset as usize >= LEDGER_PAGE_LEN && round.last_buyer != *ctx.accounts.owner.key {
+            page_index += 1;
+            page_offset = 0;
+
+            create_ledger_page(
+                ctx.accounts.owner.to_account_info(),
+                ctx.accounts.ticket_ledger.clone(),
+                ctx.accounts.system_program.to_account_info(),
+                &ctx.accounts.rent,
+                &round.key(),
+                page_index,
+                last_number,
+                _ledger_bump,
+            )?;
+        } else if page_offset > 0 && round.last_buyer == *ctx.accounts.owner.key {
+            extend_last = true;
+        }
+
+        let ticket_ledger = Loader::<TicketList>::try_from(ctx.program_id, &ctx.accounts.ticket_ledger)?;
+        check_ticket_ledger(&ticket_ledger, &round.key(), page_index)?;
+
+        let amount = round.ticket_price
+            .checked_mul(_quantity as u64)
+            .ok_or(PoolError::InvalidTicketCount)?;
+        let fee_amount = get_bps_amount(amount, pool.fee);
+        // the fee rides along into the round vault and is swept later by collect_fees
+        let total_amount = amount
+            .checked_add(fee_amount)
+            .ok_or(PoolError::InvalidTicketCount)?;
+
+        // a registered Referrer may follow the token accounts and earns part of the fee; lamport
+        // commissions go into the Referrer PDA, token ones into its commission account for the mint
+        let referrer_index = if round.mint == Pubkey::default() { 0 } else { 3 };
+        let referrer_info = ctx.remaining_accounts.get(referrer_index);
+        let mut referrer = match referrer_info {
+            Some(referrer_info) => Some(load_referrer(referrer_info, &pool.key(), ctx.accounts.owner.key)?),
+            None => None,
+        };
+        let commission = match referrer {
+            Some(_) => get_bps_amount(fee_amount, pool.referral_share),
+            None => 0,
+        };
+        let pool_fee = fee_amount - commission;
+
+        if round.mint == Pubkey::default() {
+            sol_transfer_without_seed(
+                SolTransferParamsWithoutSeed {
+                    source: ctx.accounts.owner.to_account_info(),
+                    destination: ctx.accounts.vault.clone(),
+                    system_program: ctx.accounts.system_program.to_account_info().clone(),
+                    amount: total_amount - commission,
+                }
+            )?;
+
+            if let (Some(referrer), Some(referrer_info)) = (referrer.as_mut(), referrer_info) {
+                if commission > 0 {
+                    sol_transfer_without_seed(
+                        SolTransferParamsWithoutSeed {
+                            source: ctx.accounts.owner.to_account_info(),
+                            destination: referrer_info.clone(),
+                            system_program: ctx.accounts.system_program.to_account_info().clone(),
+                            amount: commission,
+                        }
+                    )?;
+                }
+
+                referrer.referred_volume += amount;
+                referrer.earned += commission;
+                save_referrer(referrer_info, referrer)?;
+            }
+        } else {
+            let token_accounts = get_round_token_accounts(round, ctx.remaining_accounts)?;
+
+            spl_token_transfer_without_seed(
+                TokenTransferParamsWithoutSeed {
+                    source: token_accounts.token_account.clone(),
+                    destination: token_accounts.vault.clone(),
+                    authority: ctx.accounts.owner.to_account_info(),
+                    token_program: token_accounts.token_program.clone(),
+                    amount: total_amount - commission,
+                }
+            )?;
+
+            // Referrer.earned counts lamports only, a token commission is the balance of its commission account
+            if let Some(referrer_info) = referrer_info {
+                let commission_account = ctx.remaining_accounts.get(referrer_index + 1)
+                    .ok_or(PoolError::InvalidReferrer)?;
+                check_commission_account(commission_account, referrer_info.key, &round.mint, &pool.key())?;
+
+                if commission > 0 {
+                    spl_token_transfer_without_seed(
+                        TokenTransferParamsWithoutSeed {
+                            source: token_accounts.token_account.clone(),
+                            destination: commission_account.clone(),
+                            authority: ctx.accounts.owner.to_account_info(),
+                            token_program: token_accounts.token_program.clone(),
+                            amount: commission,
+                        }
+                    )?;
+                }
+            }
+        }
+
+        let ticket_data = TicketData {
+            owner : *ctx.accounts.owner.key,
+            cumulative_end : last_number + _quantity,
+        };
+
+        if extend_last {
+            set_ticket_owner(&ticket_ledger, page_offset as usize - 1, ticket_data)?;
+        } else {
+            set_ticket_owner(&ticket_ledger, page_offset as usize, ticket_data)?;
+            set_entries(&ticket_ledger, page_offset + 1)?;
+            round.page_entries = page_offset + 1;
+        }
+        round.ledger_page = page_index;
+        round.last_buyer = *ctx.accounts.owner.key;
+        round.last_number = last_number + _quantity;
+
+        // ranges are kept inline in the entry until it is full, then in overflow pages, where the
+        // last page is extended or the next one opened once it is full; player_ranges is only
+        // read once the entry overflows
+        let added_inline = player_entry.range_pages == 0
+            && add_ticket_range(&mut player_entry.ranges, last_number, _quantity);
+        if !added_inline {
+            let mut player_ranges = if ctx.accounts.player_ranges.data_is_empty() {
+                let page = create_player_ranges(
+                    ctx.accounts.owner.to_account_info(),
+                    ctx.accounts.player_ranges.clone(),
+                    ctx.accounts.system_program.to_account_info(),
+                    &ctx.accounts.rent,
+                    &round.key(),
+                    ctx.accounts.owner.key,
+                    player_entry.range_pages,
+                    _ranges_bump,
+                )?;
+                player_entry.range_pages += 1;
+                page
+            } else {
+                let page = load_player_ranges(&ctx.accounts.player_ranges, &round.key(), ctx.accounts.owner.key)?;
+                if page.page_index + 1 != player_entry.range_pages {
+                    return Err(PoolError::InvalidPlayerEntry.into());
+                }
+                page
+            };
+
+            if !add_ticket_range(&mut player_ranges.ranges, last_number, _quantity) {
+                return Err(PoolError::TicketRangesPageFull.into());
+            }
+            save_player_ranges(&ctx.accounts.player_ranges, &player_ranges)?;
+        }
+        player_entry.tickets += _quantity;
+        player_entry.total_spent += amount;
+        // only the part of the fee kept in the round vault can be refunded
+        player_entry.fees_paid += pool_fee;
+        save_player_entry(&ctx.accounts.player_entry, &player_entry)?;
+
+        round.tvl += amount;
+        round.ticket_sales += amount;
+        round.fees_collected += pool_fee;
+        pool.pending_fees += pool_fee;
+        pool.total_fees += pool_fee;
+
+        Ok(())
+    }
+
+    pub fn claim<'info> (
+        ctx : Context<'_, '_, '_, 'info, Claim<'info>>,
+        _bump : u8,
+        _place : u8,
+    ) -> ProgramResult {
+        msg!("+ claim");
+
+        let pool = &mut ctx.accounts.pool;
+        let round = &mut ctx.accounts.round;
+
+        if !round.finished {
+            return Err(PoolError::RoundNotFinished.into());
+        }
+
+        let place = _place as usize;
+        let winner = *round.winners.get(place).ok_or(PoolError::InvalidRanking)?;
+
+        if *ctx.accounts.owner.key != winner.owner {
+            return Err(PoolError::InvalidWinner.into());
+        }
+
+        // drawn winners must hold the ticket, an executed override names a winner who may hold none
+        if !winner.overridden {
+            let player_entry = load_player_entry(&ctx.accounts.player_entry, &round.key(), ctx.accounts.owner.key)?;
+            let holds_ticket = holds_ticket(&player_entry.ranges, winner.ticket_index)
+                || (player_entry.range_pages > 0 && holds_ticket(
+                    &load_player_ranges(&ctx.accounts.player_ranges, &round.key(), ctx.accounts.owner.key)?.ranges,
+                    winner.ticket_index,
+                ));
+            if !holds_ticket {
+                return Err(PoolError::InvalidWinner.into());
+            }
+        }
+
+        if winner.claimed {
+            return Err(PoolError::PrizeAlreadyClaimed.into());
+        }
+
+        if winner.disputed {
+            return Err(PoolError::PrizeDisputed.into());
+        }
+
+        if round.mint == Pubkey::default() {
+            sol_transfer(
+                SolTransferParams {
+                    source: ctx.accounts.vault.clone(),
+                    destination: ctx.accounts.owner.to_account_info(),
+                    amount: winner.amount,
+                }
+            )?;
+        } else {
+            let token_accounts = get_round_token_accounts(round, ctx.remaining_accounts)?;
+            let pool_seeds : &[&[u8]] = &[pool.rand.as_ref(), &[pool.bump]];
+
+            spl_token_transfer(
+                TokenTransferParams {
+                    source: token_accounts.vault.clone(),
+                    destination: token_accounts.token_account.clone(),
+                    authority: pool.to_account_info(),
+                    authority_signer_seeds: pool_seeds,
+                    token_program: token_accounts.token_program.clone(),
+                    amount: winner.amount,
+                }
+            )?;
+        }
+
+        round.winners[place].claimed = true;
+        round.tvl -= winner.amount;
+        round.claimed = round.winners.iter().all(|w| w.claimed);
+
+        // created only once the checks passed, so a second claim fails with PrizeAlreadyClaimed
+        // rather than on an already initialised receipt
+        let clock = Clock::from_account_info(&ctx.accounts.clock)?;
+        create_claim_receipt(
+            ctx.accounts.owner.to_account_info(),
+            ctx.accounts.receipt.clone(),
+            ctx.accounts.system_program.to_account_info(),
+            &ctx.accounts.rent,
+            &ClaimReceipt {
+                round : round.key(),
+                claimer : *ctx.accounts.owner.key,
+                place : _place,
+                ticket_index : winner.ticket_index,
+                amount : winner.amount,
+                claimed_at : clock.unix_timestamp as u64,
+                bump : _bump,
+            },
+        )
+    }
+
+    pub fn propose_override (
+        ctx : Context<ProposeOverride>,
+        _bump : u8,
+        _place : u8,
+        _new_winner : Pubkey,
+        _reason : String,
+    ) -> ProgramResult {
+        msg!("+ propose winner override");
+
+        if _reason.is_empty() || _reason.len() > MAX_REASON_LEN {
+            return Err(PoolError::InvalidOverride.into());
+        }
+
+        let override_key = get_args_key(&[
+            ctx.accounts.round.key().as_ref(),
+            &[_place],
+            _new_winner.as_ref(),
+            _reason.as_bytes(),
+        ]);
+        consume_admin_approval(
+            &ctx.accounts.pool,
+            &ctx.accounts.admin_proposal,
+            AdminAction::ProposeOverride,
+            &override_key,
+            0,
+        )?;
+
+        let round = &mut ctx.accounts.round;
+
+        if !round.finished {
+            return Err(PoolError::RoundNotFinished.into());
+        }
+
+        let place = _place as usize;
+        let winner = *round.winners.get(place).ok_or(PoolError::InvalidRanking)?;
+
+        if winner.claimed {
+            return Err(PoolError::PrizeAlreadyClaimed.into());
+        }
+
+        if winner.disputed {
+            return Err(PoolError::PrizeDisputed.into());
+        }
+
+        let clock = Clock::from_account_info(&ctx.accounts.clock)?;
+        let winner_override = &mut ctx.accounts.winner_override;
+
+        winner_override.round = round.key();
+        winner_override.place = _place;
+        winner_override.previous_winner = winner.owner;
+        winner_override.new_winner = _new_winner;
+        winner_override.reason = _reason;
+        winner_override.proposed_at = clock.unix_timestamp as u64;
+        winner_override.executable_at = clock.unix_timestamp as u64 + OVERRIDE_DELAY;
+        winner_override.executed = false;
+        winner_override.cancelled = false;
+        winner_override.bump = _bump;
+
+        round.winners[place].disputed = true;
+
+        Ok(())
+    }
+
+    pub fn execute_override (
+        ctx : Context<ExecuteOverride>,
+    ) -> ProgramResult {
+        msg!("+ execute winner override");
+
+        consume_admin_approval(
+            &ctx.accounts.pool,
+            &ctx.accounts.admin_proposal,
+            AdminAction::ExecuteOverride,
+            &ctx.accounts.winner_override.key(),
+            0,
+        )?;
+
+        let round = &mut ctx.accounts.round;
+        let winner_override = &mut ctx.accounts.winner_override;
+
+        if winner_override.executed || winner_override.cancelled {
+            return Err(PoolError::InvalidOverride.into());
+        }
+
+        let clock = Clock::from_account_info(&ctx.accounts.clock)?;
+        let now = clock.unix_timestamp as u64;
+        if now < winner_override.executable_at
+            || now >= winner_override.executable_at + OVERRIDE_GRACE_PERIOD
+        {
+            return Err(PoolError::InvalidTime.into());
+        }
+
+        let place = winner_override.place as usize;
+        round.winners[place].owner = winner_override.new_winner;
+        round.winners[place].overridden = true;
+        round.winners[place].disputed = false;
+
+        winner_override.executed = true;
+
+        Ok(())
+    }
+
+    pub fn cancel_override (
+        ctx : Context<ExecuteOverride>,
+    ) -> ProgramResult {
+        msg!("+ cancel winner override");
+
+        consume_admin_approval(
+            &ctx.accounts.pool,
+            &ctx.accounts.admin_proposal,
+            AdminAction::CancelOverride,
+            &ctx.accounts.winner_override.key(),
+            0,
+        )?;
+
+        let round = &mut ctx.accounts.round;
+        let winner_override = &mut ctx.accounts.winner_override;
+
+        if winner_override.executed || winner_override.cancelled {
+            return Err(PoolError::InvalidOverride.into());
+        }
+
+        round.winners[winner_override.place as usize].disputed = false;
+
+        winner_override.cancelled = true;
+
+        Ok(())
+    }
+
+    // anyone may lift a dispute the owner neither executed nor cancelled in time; the override PDA
+    // stays behind, so a place is disputed at most once and disputes cannot be chained to block a claim
+    pub fn expire_override (
+        ctx : Context<ExpireOverride>,
+    ) -> ProgramResult {
+        msg!("+ expire winner override");
+
+        let round = &mut ctx.accounts.round;
+        let winner_override = &mut ctx.accounts.winner_override;
+
+        if winner_override.executed || winner_override.cancelled {
+            return Err(PoolError::InvalidOverride.into());
+        }
+
+        let clock = Clock::from_account_info(&ctx.accounts.clock)?;
+        if (clock.unix_timestamp as u64) < winner_override.executable_at + OVERRIDE_GRACE_PERIOD {
+            return Err(PoolError::InvalidTime.into());
+        }
+
+        round.winners[winner_override.place as usize].disputed = false;
+
+        winner_override.cancelled = true;
+
+        Ok(())
+    }
+
+    pub fn cancel_round<'info> (
+        ctx : Context<'_, '_, '_, 'info, CancelRound<'info>>,
+        _refund_fees : bool,
+    ) -> ProgramResult {
+        msg!("+ cancel round");
+
+        let pool = &ctx.accounts.pool;
+        let round = &mut ctx.accounts.round;
+
+        if round.finished || round.cancelled {
+            return Err(PoolError::RoundAlreadyFinished.into());
+        }
+
+        // the owner may abort until the round is bound to a draw, since a revealed seed or an oracle
+        // request can make the outcome known before anyone finishes the round. After that, and for
+        // anyone else, a round can only be cancelled once sales closed under min_tickets, when the
+        // owner let REVEAL_GRACE_PERIOD pass without revealing or the oracle without answering,
+        // or when nobody drew the round before its draw slot left SlotHashes
+        let draw_bound = round.revealed || round.oracle != Pubkey::default();
+        let by_owner = cmp_pubkeys(ctx.accounts.caller.key, &pool.owner) && !draw_bound;
+        if !by_owner {
+            let clock = Clock::from_account_info(&ctx.accounts.clock)?;
+            let now = clock.unix_timestamp as u64;
+            let deadline_passed = now >= get_round_end_time(round) + REVEAL_GRACE_PERIOD;
+            let under_subscribed = is_sales_closed(round, now) && round.last_number < round.min_tickets;
+            let reveal_expired = !draw_bound && deadline_passed;
+            let oracle_expired = round.oracle != Pubkey::default()
+                && deadline_passed
+                && is_oracle_unanswered(round, &pool.oracle_program, ctx.remaining_accounts.first())?;
+            let draw_expired = is_draw_expired(round, clock.slot);
+            if !under_subscribed && !reveal_expired && !oracle_expired && !draw_expired {
+                return Err(PoolError::InvalidTime.into());
+            }
+        }
+
+        // only the owner can promise fee refunds, paid from fees not yet swept from the round,
+        // so once a sweep took some of them the promise could not be kept for every player
+        let refund_fees = by_owner && _refund_fees;
+        if refund_fees && round.fees_claimed > 0 {
+            return Err(PoolError::FeesAlreadyCollected.into());
+        }
+
+        // the deadline paths are open to anyone, only the owner's discretionary abort needs approval
+        if by_owner {
+            consume_admin_approval(
+                pool,
+                &ctx.accounts.admin_proposal,
+                AdminAction::CancelRound,
+                &round.key(),
+                refund_fees as u64,
+            )?;
+        }
+
+        round.cancelled = true;
+        round.refund_fees = refund_fees;
+
+        Ok(())
+    }
+
+    pub fn refund<'info> (
+        ctx : Context<'_, '_, '_, 'info, Refund<'info>>,
+    ) -> ProgramResult {
+        msg!("+ refund");
+
+        let pool = &mut ctx.accounts.pool;
+        let round = &mut ctx.accounts.round;
+
+        if !round.cancelled {
+            return Err(PoolError::RoundNotCancelled.into());
+        }
+
+        let mut player_entry = load_player_entry(&ctx.accounts.player_entry, &round.key(), ctx.accounts.owner.key)?;
+        if player_entry.refunded || player_entry.tickets == 0 {
+            return Err(PoolError::AlreadyRefunded.into());
+        }
+
+        // ticket money comes out of tvl, a refunded fee out of the fees not yet swept from this round
+        let fee_refund = if round.refund_fees { player_entry.fees_paid } else { 0 };
+        if fee_refund > round.fees_collected - round.fees_claimed {
+            return Err(PoolError::InsufficientRoundFunds.into());
+        }
+
+        round.tvl = round.tvl.checked_sub(player_entry.total_spent).ok_or(PoolError::InsufficientRoundFunds)?;
+        round.refunded_amount += player_entry.total_spent;
+        round.fees_collected -= fee_refund;
+        pool.pending_fees -= fee_refund;
+        pool.total_fees -= fee_refund;
+
+        let amount = player_entry.total_spent + fee_refund;
+
+        if round.mint == Pubkey::default() {
+            sol_transfer(
+                SolTransferParams {
+                    source: ctx.accounts.vault.clone(),
+                    destination: ctx.accounts.owner.to_account_info(),
+                    amount,
+                }
+            )?;
+        } else {
+            let token_accounts = get_round_token_accounts(round, ctx.remaining_accounts)?;
+            let pool_seeds : &[&[u8]] = &[pool.rand.as_ref(), &[pool.bump]];
+
+            spl_token_transfer(
+                TokenTransferParams {
+                    source: token_accounts.vault.clone(),
+                    destination: token_accounts.token_account.clone(),
+                    authority: pool.to_account_info(),
+                    authority_signer_seeds: pool_seeds,
+                    token_program: token_accounts.token_program.clone(),
+                    amount,
+                }
+            )?;
+        }
+
+        player_entry.refunded = true;
+        save_player_entry(&ctx.accounts.player_entry, &player_entry)
+    }
+
+    pub fn collect_fees<'info> (
+        ctx : Context<'_, '_, '_, 'info, CollectFees<'info>>,
+    ) -> ProgramResult {
+        msg!("+ collect fees");
+
+        let pool = &mut ctx.accounts.pool;
+        let round = &mut ctx.accounts.round;
+
+        // fees promised back to players stay put until the refunds are done
+        if round.cancelled && round.refund_fees {
+            return Err(PoolError::RoundCancelled.into());
+        }
+
+        let amount = round.fees_collected - round.fees_claimed;
+        if amount == 0 {
+            return Err(PoolError::NoFeesToCollect.into());
+        }
+
+        // split by the fee schedule, the last recipient also takes the rounding dust
+        let recipients = pool.fee_recipients.clone();
+        let mut remaining = amount;
+        if round.mint == Pubkey::default() {
+            // recipient wallets are passed as remaining accounts in schedule order
+            if ctx.remaining_accounts.len() < recipients.len() {
+                return Err(PoolError::InvalidFeeRecipient.into());
+            }
+
+            for (i, fee_recipient) in recipients.iter().enumerate() {
+                let destination = &ctx.remaining_accounts[i];
+                if !cmp_pubkeys(destination.key, &fee_recipient.recipient) {
+                    return Err(PoolError::InvalidFeeRecipient.into());
+                }
+
+                let share = if i + 1 == recipients.len() {
+                    remaining
+                } else {
+                    get_bps_amount(amount, fee_recipient.share as u64)
+                };
+                remaining -= share;
+
+                sol_transfer(
+                    SolTransferParams {
+                        source: ctx.accounts.vault.clone(),
+                        destination: destination.clone(),
+                        amount: share,
+                    }
+                )?;
+            }
+        } else {
+            // token rounds pass [first recipient token, vault, token program, other recipient tokens...]
+            let token_accounts = get_round_token_accounts(round, ctx.remaining_accounts)?;
+            let pool_seeds : &[&[u8]] = &[pool.rand.as_ref(), &[pool.bump]];
+            if ctx.remaining_accounts.len() < recipients.len() + 2 {
+                return Err(PoolError::InvalidFeeRecipient.into());
+            }
+
+            for (i, fee_recipient) in recipients.iter().enumerate() {
+                let destination = if i == 0 {
+                    token_accounts.token_account.clone()
+                } else {
+                    ctx.remaining_accounts[i + 2].clone()
+                };
+                let destination_data = check_token_account(&destination, &round.mint)?;
+                if destination_data.owner != fee_recipient.recipient {
+                    return Err(PoolError::InvalidFeeRecipient.into());
+                }
+
+                let share = if i + 1 == recipients.len() {
+                    remaining
+                } else {
+                    get_bps_amount(amount, fee_recipient.share as u64)
+                };
+                remaining -= share;
+
+                spl_token_transfer(
+                    TokenTransferParams {
+                        source: token_accounts.vault.clone(),
+                        destination,
+                        authority: pool.to_account_info(),
+                        authority_signer_seeds: pool_seeds,
+                        token_program: token_accounts.token_program.clone(),
+                        amount: share,
+                    }
+                )?;
+            }
+        }
+
+        round.fees_claimed += amount;
+        pool.pending_fees -= amount;
+        pool.collected_fees += amount;
+
+        Ok(())
+    }
+
+    pub fn withdraw<'info> (
+        ctx : Context<'_, '_, '_, 'info, Withdraw<'info>>,
+        _amount : u64
+    ) -> ProgramResult {
+        msg!("+ withdraw");
+
+        consume_admin_approval(
+            &ctx.accounts.pool,
+            &ctx.accounts.admin_proposal,
+            AdminAction::Withdraw,
+            &ctx.accounts.round.key(),
+            _amount,
+        )?;
+
+        let pool = &mut ctx.accounts.pool;
+        let round = &mut ctx.accounts.round;
+
+        let surplus = get_round_surplus(round)?;
+        if _amount == 0 || _amount > surplus {
+            return Err(PoolError::InvalidWithdrawAmount.into());
+        }
+
+        if round.mint == Pubkey::default() {
+            // the escrow must stay rent exempt, whatever tvl says
+            let vault = &ctx.accounts.vault;
+            let min_balance = ctx.accounts.rent.minimum_balance(vault.data_len());
+            if vault.lamports().saturating_sub(_amount) < min_balance {
+                return Err(PoolError::InvalidWithdrawAmount.into());
+            }
+
+            sol_transfer(
+                SolTransferParams {
+                    source: ctx.accounts.vault.clone(),
+                    destination: ctx.accounts.owner.to_account_info(),
+                    amount: _amount,
+                }
+            )?;
+        } else {
+            let token_accounts = get_round_token_accounts(round, ctx.remaining_accounts)?;
+            let pool_seeds : &[&[u8]] = &[pool.rand.as_ref(), &[pool.bump]];
+
+            spl_token_transfer(
+                TokenTransferParams {
+                    source: token_accounts.vault.clone(),
+                    destination: token_accounts.token_account.clone(),
+                    authority: pool.to_account_info(),
+                    authority_signer_seeds: pool_seeds,
+                    token_program: token_accounts.token_program.clone(),
+                    amount: _amount,
+                }
+            )?;
+        }
+        
+        round.tvl -= _amount;
+
+        Ok(())
+    }
+
+    pub fn deposit<'info> (
+        ctx : Context<'_, '_, '_, 'info, Deposit<'info>>,
+        _amount : u64
+    ) -> ProgramResult {
+        msg!("+ deposit");
+
+        consume_admin_approval(
+            &ctx.accounts.pool,
+            &ctx.accounts.admin_proposal,
+            AdminAction::Deposit,
+            &ctx.accounts.round.key(),
+            _amount,
+        )?;
+
+        let round = &mut ctx.accounts.round;
+
+        if round.mint == Pubkey::default() {
+            sol_transfer_without_seed(
+                SolTransferParamsWithoutSeed {
+                    source: ctx.accounts.owner.to_account_info(),
+                    destination: ctx.accounts.vault.clone(),
+                    system_program: ctx.accounts.system_program.to_account_info().clone(),
+                    amount: _amount,
+                }
+            )?;
+        } else {
+            let token_accounts = get_round_token_accounts(round, ctx.remaining_accounts)?;
+
+            spl_token_transfer_without_seed(
+                TokenTransferParamsWithoutSeed {
+                    source: token_accounts.token_account.clone(),
+                    destination: token_accounts.vault.clone(),
+                    authority: ctx.accounts.owner.to_account_info(),
+                    token_program: token_accounts.token_program.clone(),
+                    amount: _amount,
+                }
+            )?;
+        }
+        
+        round.tvl += _amount;
+
+        Ok(())
+    }
+}
+
+
+#[derive(Accounts)]
+#[instruction(_bump : u8)]
+pub struct InitPool<'info>{
+    #[account(mut)]
+    owner : Signer<'info>,
+
+    #[account(init,
+        seeds = [(*rand.key).as_ref()], 
+        bump = _bump, 
+        payer = owner, 
+        space = 8 + POOL_SIZE)]
+    pool : ProgramAccount<'info, Pool>,
+
+    rand : AccountInfo<'info>,
+
+    fee_receiver : AccountInfo<'info>,
+
+    system_program : Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(_bump : u8, _round_name: String)]
+pub struct StartRound<'info>{
+    #[account(mut)]
+    owner : Signer<'info>,
+
+    #[account(mut,
+        has_one = owner)]
+    pool : ProgramAccount<'info, Pool>,
+
+    #[account(mut)]
+    ticket_ledger : AccountInfo<'info>,
+
+    // lamport escrow PDA [round, "escrow"], replaced by the token vault in init_round_vault
+    #[account(mut)]
+    vault : AccountInfo<'info>,
+
+    #[account(init, 
+        seeds = [pool.key().as_ref(), _round_name.as_ref()], 
+        bump = _bump, 
+        payer = owner, 
+        space = 8 + ROUND_SIZE)]
+    round_data : ProgramAccount<'info, Round>,
+
+    #[account(mut)]
+    admin_proposal : AccountInfo<'info>,
+
+    clock : AccountInfo<'info>,
+
+    rent : Sysvar<'info, Rent>,
+
+    system_program : Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(_bump : u8)]
+pub struct InitRoundVault<'info>{
+    #[account(mut)]
+    owner : Signer<'info>,
+
+    #[account(has_one = owner)]
+    pool : ProgramAccount<'info, Pool>,
+
+    #[account(mut,
+        has_one = pool,
+        seeds = [round.pool.key().as_ref(), round.round_name.as_ref()], 
+        bump = round.bump)]
+    round : ProgramAccount<'info, Round>,
+
+    mint : Account<'info, Mint>,
+
+    #[account(init,
+        seeds = [round.key().as_ref(), b"vault".as_ref()], 
+        bump = _bump, 
+        payer = owner,
+        token::mint = mint,
+        token::authority = pool)]
+    vault : Account<'info, TokenAccount>,
+
+    // the round's lamport escrow, closed here
+    #[account(mut)]
+    escrow : AccountInfo<'info>,
+
+    #[account(mut)]
+    admin_proposal : AccountInfo<'info>,
+
+    token_program : Program<'info, Token>,
+
+    system_program : Program<'info, System>,
+
+    rent : Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct SetOracleProgram<'info>{
+    owner : Signer<'info>,
+
+    #[account(mut,
+        has_one = owner)]
+    pool : ProgramAccount<'info, Pool>,
+
+    #[account(executable)]
+    oracle_program : AccountInfo<'info>,
+
+    #[account(mut)]
+    admin_proposal : AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct UpdatePoolConfig<'info>{
+    owner : Signer<'info>,
+
+    #[account(mut,
+        has_one = owner)]
+    pool : ProgramAccount<'info, Pool>,
+
+    fee_receiver : AccountInfo<'info>,
+
+    #[account(mut)]
+    admin_proposal : AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ProposeOwner<'info>{
+    owner : Signer<'info>,
+
+    #[account(mut,
+        has_one = owner)]
+    pool : ProgramAccount<'info, Pool>,
+}
+
+#[derive(Accounts)]
+pub struct AcceptOwner<'info>{
+    pending_owner : Signer<'info>,
+
+    #[account(mut,
+        has_one = pending_owner)]
+    pool : ProgramAccount<'info, Pool>,
+}
+
+#[derive(Accounts)]
+pub struct SetAdmins<'info>{
+    owner : Signer<'info>,
+
+    #[account(mut,
+        has_one = owner)]
+    pool : ProgramAccount<'info, Pool>,
+
+    #[account(mut)]
+    admin_proposal : AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(_bump : u8)]
+pub struct ProposeAdminAction<'info>{
+    #[account(mut)]
+    admin : Signer<'info>,
+
+    #[account(mut)]
+    pool : ProgramAccount<'info, Pool>,
+
+    #[account(init,
+        seeds = [pool.key().as_ref(), b"proposal".as_ref(), &pool.proposal_count.to_le_bytes()], 
+        bump = _bump, 
+        payer = admin, 
+        space = 8 + ADMIN_PROPOSAL_SIZE)]
+    proposal : ProgramAccount<'info, AdminProposal>,
+
+    system_program : Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ApproveAdminAction<'info>{
+    admin : Signer<'info>,
+
+    pool : ProgramAccount<'info, Pool>,
+
+    #[account(mut,
+        has_one = pool,
+        seeds = [pool.key().as_ref(), b"proposal".as_ref(), &proposal.index.to_le_bytes()], 
+        bump = proposal.bump)]
+    proposal : ProgramAccount<'info, AdminProposal>,
+}
+
+#[derive(Accounts)]
+pub struct SetFeeRecipients<'info>{
+    owner : Signer<'info>,
+
+    #[account(mut,
+        has_one = owner)]
+    pool : ProgramAccount<'info, Pool>,
+
+    #[account(mut)]
+    admin_proposal : AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetReferralShare<'info>{
+    owner : Signer<'info>,
+
+    #[account(mut,
+        has_one = owner)]
+    pool : ProgramAccount<'info, Pool>,
+
+    #[account(mut)]
+    admin_proposal : AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(_bump : u8)]
+pub struct RegisterReferrer<'info>{
+    #[account(mut)]
+    owner : Signer<'info>,
+
+    pool : ProgramAccount<'info, Pool>,
+
+    #[account(init,
+        seeds = [pool.key().as_ref(), b"referrer".as_ref(), owner.key.as_ref()], 
+        bump = _bump, 
+        payer = owner, 
+        space = 8 + REFERRER_SIZE)]
+    referrer : ProgramAccount<'info, Referrer>,
+
+    system_program : Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(_bump : u8)]
+pub struct InitCommissionAccount<'info>{
+    #[account(mut)]
+    owner : Signer<'info>,
+
+    pool : ProgramAccount<'info, Pool>,
+
+    #[account(has_one = pool,
+        has_one = owner,
+        seeds = [pool.key().as_ref(), b"referrer".as_ref(), owner.key.as_ref()], 
+        bump = referrer.bump)]
+    referrer : ProgramAccount<'info, Referrer>,
+
+    mint : Account<'info, Mint>,
+
+    #[account(init,
+        seeds = [referrer.key().as_ref(), b"commission".as_ref(), mint.key().as_ref()], 
+        bump = _bump, 
+        payer = owner,
+        token::mint = mint,
+        token::authority = pool)]
+    commission : Account<'info, TokenAccount>,
+
+    token_program : Program<'info, Token>,
+
+    system_program : Program<'info, System>,
+
+    rent : Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+#[instruction(_bump : u8)]
+pub struct ClaimReferralTokenRewards<'info>{
+    owner : Signer<'info>,
+
+    pool : ProgramAccount<'info, Pool>,
+
+    #[account(has_one = pool,
+        has_one = owner,
+        seeds = [pool.key().as_ref(), b"referrer".as_ref(), owner.key.as_ref()], 
+        bump = referrer.bump)]
+    referrer : ProgramAccount<'info, Referrer>,
+
+    #[account(mut,
+        seeds = [referrer.key().as_ref(), b"commission".as_ref(), commission.mint.as_ref()], 
+        bump = _bump)]
+    commission : Account<'info, TokenAccount>,
+
+    // the referrer's own account for the same mint
+    #[account(mut)]
+    token_account : Account<'info, TokenAccount>,
+
+    token_program : Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimReferralRewards<'info>{
+    #[account(mut)]
+    owner : Signer<'info>,
+
+    #[account(mut,
+        has_one = owner,
+        seeds = [referrer.pool.as_ref(), b"referrer".as_ref(), owner.key.as_ref()], 
+        bump = referrer.bump)]
+    referrer : ProgramAccount<'info, Referrer>,
+}
+
+#[derive(Accounts)]
+pub struct RequestRandomness<'info>{
+    owner : Signer<'info>,
+
+    #[account(has_one = owner)]
+    pool : ProgramAccount<'info, Pool>,
+
+    #[account(mut,
+        has_one = pool,
+        seeds = [round.pool.key().as_ref(), round.round_name.as_ref()], 
+        bump = round.bump)]
+    round : ProgramAccount<'info, Round>,
+
+    oracle : AccountInfo<'info>,
+
+    clock : AccountInfo<'info>,
+
+    #[account(mut)]
+    admin_proposal : AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ConsumeRandomness<'info>{
+    pool : ProgramAccount<'info, Pool>,
+
+    #[account(mut,
+        has_one = pool,
+        has_one = oracle,
+        seeds = [round.pool.key().as_ref(), round.round_name.as_ref()], 
+        bump = round.bump)]
+    round : ProgramAccount<'info, Round>,
+
+    oracle : AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct RevealRound<'info>{
+    owner : Signer<'info>,
+
+    #[account(has_one = owner)]
+    pool : ProgramAccount<'info, Pool>,
+
+    #[account(mut,
+        has_one = pool,
+        seeds = [round.pool.key().as_ref(), round.round_name.as_ref()], 
+        bump = round.bump)]
+    round : ProgramAccount<'info, Round>,
+
+    clock : AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct FinishRound<'info>{
+    #[account(mut)]
+    owner : Signer<'info>,
+
+    #[account(mut,
+        has_one = owner)]
+    pool : ProgramAccount<'info, Pool>,
+
+    #[account(mut,
+        has_one = pool,
+        seeds = [round.pool.key().as_ref(), round.round_name.as_ref()], 
+        bump = round.bump)]
+    round : ProgramAccount<'info, Round>,
+
+    /// CHECK: checked in program.
+    slot_hashes: UncheckedAccount<'info>,
+
+    clock : AccountInfo<'info>,
+
+    #[account(mut)]
+    admin_proposal : AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CrankFinishRound<'info>{
+    #[account(mut)]
+    caller : Signer<'info>,
+
+    #[account(mut)]
+    pool : ProgramAccount<'info, Pool>,
+
+    #[account(mut,
+        has_one = pool,
+        has_one = vault,
+        seeds = [round.pool.key().as_ref(), round.round_name.as_ref()], 
+        bump = round.bump)]
+    round : ProgramAccount<'info, Round>,
+
+    #[account(mut)]
+    vault : AccountInfo<'info>,
+
+    /// CHECK: checked in program.
+    slot_hashes: UncheckedAccount<'info>,
+
+    clock : AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct BuyTicket<'info> {
+    #[account(mut)]
+    owner : Signer<'info>,
+
+    #[account(mut)]
+    pool : ProgramAccount<'info, Pool>,
+
+    #[account(mut,
+        has_one = pool,
+        has_one = vault,
+        seeds = [round.pool.key().as_ref(), round.round_name.as_ref()], 
+        bump = round.bump)]
+    round : ProgramAccount<'info, Round>,
+
+    #[account(mut)]
+    vault : AccountInfo<'info>,
+
+    // current ledger page, created here when this ticket opens a new page
+    #[account(mut)]
+    ticket_ledger : AccountInfo<'info>,
+
+    // PDA [round, owner], created here on the player's first purchase
+    #[account(mut)]
+    player_entry : AccountInfo<'info>,
+
+    // once the entry's inline ranges are full, the player's last overflow page or the next one to be created here
+    #[account(mut)]
+    player_ranges : AccountInfo<'info>,
+
+    clock : AccountInfo<'info>,
+
+    rent : Sysvar<'info, Rent>,
+
+    system_program : Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct Claim<'info> {
+    #[account(mut)]
+    owner : Signer<'info>,
+
+    #[account(mut)]
+    pool : ProgramAccount<'info, Pool>,
+
+    #[account(mut,
+        has_one = pool,
+        has_one = vault,
+        seeds = [round.pool.key().as_ref(), round.round_name.as_ref()], 
+        bump = round.bump)]
+    round : ProgramAccount<'info, Round>,
+
+    #[account(mut)]
+    vault : AccountInfo<'info>,
+
+    // PDA [round, "receipt", place], created here once the claim is paid
+    #[account(mut)]
+    receipt : AccountInfo<'info>,
+
+    // claimer's PDA [round, owner], and the overflow ranges page holding the winning ticket when
+    // the entry's inline ranges do not; neither is read for overridden places
+    player_entry : AccountInfo<'info>,
+
+    player_ranges : AccountInfo<'info>,
+
+    clock : AccountInfo<'info>,
+
+    rent : Sysvar<'info, Rent>,
+
+    system_program : Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(_bump : u8, _place : u8)]
+pub struct ProposeOverride<'info> {
+    #[account(mut)]
+    owner : Signer<'info>,
+
+    #[account(has_one = owner)]
+    pool : ProgramAccount<'info, Pool>,
+
+    #[account(mut,
+        has_one = pool,
+        seeds = [round.pool.key().as_ref(), round.round_name.as_ref()], 
+        bump = round.bump)]
+    round : ProgramAccount<'info, Round>,
+
+    #[account(init,
+        seeds = [round.key().as_ref(), b"override".as_ref(), &[_place]], 
+        bump = _bump, 
+        payer = owner, 
+        space = 8 + WINNER_OVERRIDE_SIZE)]
+    winner_override : ProgramAccount<'info, WinnerOverride>,
+
+    clock : AccountInfo<'info>,
+
+    #[account(mut)]
+    admin_proposal : AccountInfo<'info>,
+
+    system_program : Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ExecuteOverride<'info> {
+    owner : Signer<'info>,
+
+    #[account(has_one = owner)]
+    pool : ProgramAccount<'info, Pool>,
+
+    #[account(mut,
+        has_one = pool,
+        seeds = [round.pool.key().as_ref(), round.round_name.as_ref()], 
+        bump = round.bump)]
+    round : ProgramAccount<'info, Round>,
+
+    #[account(mut,
+        has_one = round,
+        seeds = [round.key().as_ref(), b"override".as_ref(), &[winner_override.place]], 
+        bump = winner_override.bump)]
+    winner_override : ProgramAccount<'info, WinnerOverride>,
+
+    clock : AccountInfo<'info>,
+
+    #[account(mut)]
+    admin_proposal : AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ExpireOverride<'info> {
+    caller : Signer<'info>,
+
+    pool : ProgramAccount<'info, Pool>,
+
+    #[account(mut,
+        has_one = pool,
+        seeds = [round.pool.key().as_ref(), round.round_name.as_ref()], 
+        bump = round.bump)]
+    round : ProgramAccount<'info, Round>,
+
+    #[account(mut,
+        has_one = round,
+        seeds = [round.key().as_ref(), b"override".as_ref(), &[winner_override.place]], 
+        bump = winner_override.bump)]
+    winner_override : ProgramAccount<'info, WinnerOverride>,
+
+    clock : AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CancelRound<'info> {
+    caller : Signer<'info>,
+
+    pool : ProgramAccount<'info, Pool>,
+
+    #[account(mut,
+        has_one = pool,
+        seeds = [round.pool.key().as_ref(), round.round_name.as_ref()], 
+        bump = round.bump)]
+    round : ProgramAccount<'info, Round>,
+
+    clock : AccountInfo<'info>,
+
+    // read only when the owner cancels, any account will do on the deadline paths
+    #[account(mut)]
+    admin_proposal : AccountInfo<'info>,
+
+    // a round waiting on the oracle passes its oracle account after these
+}
+
+#[derive(Accounts)]
+pub struct Refund<'info> {
+    #[account(mut)]
+    owner : Signer<'info>,
+
+    #[account(mut)]
+    pool : ProgramAccount<'info, Pool>,
+
+    #[account(mut,
+        has_one = pool,
+        has_one = vault,
+        seeds = [round.pool.key().as_ref(), round.round_name.as_ref()], 
+        bump = round.bump)]
+    round : ProgramAccount<'info, Round>,
+
+    #[account(mut)]
+    vault : AccountInfo<'info>,
+
+    // PDA [round, owner]
+    #[account(mut)]
+    player_entry : AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CollectFees<'info> {
+    #[account(mut)]
+    fee_receiver : Signer<'info>,
+
+    #[account(mut,
+        has_one = fee_receiver)]
+    pool : ProgramAccount<'info, Pool>,
+
+    #[account(mut,
+        has_one = pool,
+        has_one = vault,
+        seeds = [round.pool.key().as_ref(), round.round_name.as_ref()], 
+        bump = round.bump)]
+    round : ProgramAccount<'info, Round>,
+
+    #[account(mut)]
+    vault : AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct Withdraw<'info> {
+    #[account(mut)]
+    owner : Signer<'info>,
+
+    #[account(mut,
+        has_one = owner)]
+    pool : ProgramAccount<'info, Pool>,
+
+    #[account(mut,
+        has_one = pool,
+        has_one = vault,
+        seeds = [round.pool.key().as_ref(), round.round_name.as_ref()], 
+        bump = round.bump)]
+    round : ProgramAccount<'info, Round>,
+
+    #[account(mut)]
+    vault : AccountInfo<'info>,
+
+    #[account(mut)]
+    admin_proposal : AccountInfo<'info>,
+
+    rent : Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct Deposit<'info> {
+    #[account(mut)]
+    owner : Signer<'info>,
+
+    #[account(mut,
+        has_one = owner)]
     pool : ProgramAccount<'info, Pool>,
 
-    #[account(mut,
-        has_one = pool,
-        seeds = [round.pool.key().as_ref(), round.round_name.as_ref()], 
-        bump = round.bump)]
-    round : ProgramAccount<'info, Round>
+    #[account(mut,
+        has_one = pool,
+        has_one = vault,
+        seeds = [round.pool.key().as_ref(), round.round_name.as_ref()], 
+        bump = round.bump)]
+    round : ProgramAccount<'info, Round>,
+
+    #[account(mut)]
+    vault : AccountInfo<'info>,
+
+    #[account(mut)]
+    admin_proposal : AccountInfo<'info>,
+
+    system_program : Program<'info, System>,
+}
+
+pub const FEE_DENOMINATOR : u64 = 10000;
+
+pub const MAX_CRANK_REWARD : u64 = 100;
+
+pub const MAX_FEE_RECIPIENTS : usize = 5;
+
+pub const MAX_ADMINS : usize = 5;
+
+pub const POOL_SIZE : usize = 32 + 32 + 32 + 32 + 4 + FEE_RECIPIENT_SIZE * MAX_FEE_RECIPIENTS + 8 + 8 + 32 + 8 + 8 + 8 + 8
+    + 4 + 32 * MAX_ADMINS + 1 + 8 + 1;
+#[account]
+pub struct Pool {
+    pub owner : Pubkey,
+    // set by propose_owner, becomes owner once it signs accept_owner
+    pub pending_owner : Pubkey,
+    pub rand : Pubkey,
+    // signs collect_fees
+    pub fee_receiver : Pubkey,
+    // collected fees are split between these, shares sum to FEE_DENOMINATOR
+    pub fee_recipients : Vec<FeeRecipient>,
+    // fee charged on top of the ticket price, in basis points
+    pub fee : u64,
+    // part of the fee paid to the buyer's referrer, in basis points of the fee
+    pub referral_share : u64,
+    pub oracle_program : Pubkey,
+    // share of the round tvl paid to whoever cranks finish_round, in basis points
+    pub crank_reward : u64,
+    // fees sitting in round vaults, and lifetime totals accrued and swept by collect_fees
+    pub pending_fees : u64,
+    pub total_fees : u64,
+    pub collected_fees : u64,
+    // with a threshold above 0, privileged actions need that many admin approvals
+    pub admins : Vec<Pubkey>,
+    pub threshold : u8,
+    pub proposal_count : u64,
+    pub bump : u8,
+}
+
+#[event]
+pub struct PoolConfigUpdated {
+    pub pool : Pubkey,
+    pub fee : u64,
+    pub crank_reward : u64,
+    pub fee_receiver : Pubkey,
+    pub recipients : Vec<FeeRecipient>,
+}
+
+#[event]
+pub struct FeeRecipientsUpdated {
+    pub pool : Pubkey,
+    pub recipients : Vec<FeeRecipient>,
+}
+
+#[event]
+pub struct ReferralShareUpdated {
+    pub pool : Pubkey,
+    pub referral_share : u64,
+}
+
+#[event]
+pub struct OwnerProposed {
+    pub pool : Pubkey,
+    pub owner : Pubkey,
+    pub pending_owner : Pubkey,
+}
+
+#[event]
+pub struct OwnerChanged {
+    pub pool : Pubkey,
+    pub previous_owner : Pubkey,
+    pub owner : Pubkey,
+}
+
+pub const MIN_ROUND_PERIOD : u64 = 60;
+pub const MAX_ROUND_PERIOD : u64 = 60 * 60 * 24 * 30;
+pub const REVEAL_GRACE_PERIOD : u64 = 60 * 60 * 24;
+// slots between the reveal and the slot whose hash seeds the draw
+pub const DRAW_SLOT_DELAY : u64 = 10;
+// SlotHashes keeps this many recent slots
+pub const SLOT_HASHES_LEN : usize = 512;
+
+pub const MAX_TIERS : usize = 5;
+pub const MAX_WINNERS : usize = 10;
+
+pub const ROUND_SIZE : usize = 32 + 4 + 4 + 4 + 32 + 8 + 4 + 4 + 8 + 8 + 8 + 8 + 8 + 8 + 8 + 8 + 1 + 1 + 1 + 1 + 4 + 10 + 32 + 32 + 32 + 32 + 1 + 8 + 32
+    + 4 + PRIZE_TIER_SIZE * MAX_TIERS + 4 + WINNER_SIZE * MAX_WINNERS + 1;
+#[account]
+pub struct Round {
+    pub pool : Pubkey,
+    // tickets sold so far, across all ledger pages
+    pub last_number : u32,
+    // page currently being filled and the purchases recorded on it
+    pub ledger_page : u32,
+    pub page_entries : u32,
+    // owner of the latest run, extended in place when they buy again
+    pub last_buyer : Pubkey,
+    pub total_ticket : u64,
+    // 0 leaves purchases per wallet uncapped
+    pub max_tickets_per_wallet : u32,
+    // rounds closing below this may be cancelled by anyone
+    pub min_tickets : u32,
+    pub ticket_price : u64,
+    pub start_time : u64,
+    pub round_period : u64,
+    pub tvl : u64,
+    // ticket revenue and fees paid by players, and what refund already paid back
+    pub ticket_sales : u64,
+    pub fees_collected : u64,
+    pub refunded_amount : u64,
+    // fees already swept to the fee receiver
+    pub fees_claimed : u64,
+    pub claimed : bool,
+    pub finished : bool,
+    pub cancelled : bool,
+    pub refund_fees : bool,
+    pub round_name : String,
+    // Pubkey::default() for rounds paid in lamports
+    pub mint : Pubkey,
+    // lamport escrow [round, "escrow"], or the token vault once init_round_vault ran
+    pub vault : Pubkey,
+    // hash(pool.rand, round, reveal) committed by the owner at start_round
+    pub commitment : [u8; 32],
+    pub reveal : [u8; 32],
+    pub revealed : bool,
+    // slot fixed by reveal_round whose hash seeds the draw
+    pub draw_slot : u64,
+    // pending randomness request, Pubkey::default() when none
+    pub oracle : Pubkey,
+    pub tiers : Vec<PrizeTier>,
+    // one entry per drawn place, in tier order
+    pub winners : Vec<Winner>,
+    pub bump : u8
+}
+
+pub const FEE_RECIPIENT_SIZE : usize = 32 + 2;
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy)]
+pub struct FeeRecipient {
+    pub recipient : Pubkey,
+    // basis points of every fee sweep
+    pub share : u16,
+}
+
+pub const PRIZE_TIER_SIZE : usize = 1 + 2;
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy)]
+pub struct PrizeTier {
+    pub winners : u8,
+    // share of the prize pot paid to each winner of this tier, in basis points
+    pub share : u16,
+}
+
+pub const WINNER_SIZE : usize = 4 + 32 + 8 + 1 + 1 + 1;
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy)]
+pub struct Winner {
+    pub ticket_index : u32,
+    pub owner : Pubkey,
+    pub amount : u64,
+    pub claimed : bool,
+    // a pending override blocks the claim until it is executed, cancelled or expired
+    pub disputed : bool,
+    pub overridden : bool,
+}
+
+pub const OVERRIDE_DELAY : u64 = 60 * 60 * 24 * 2;
+// an override left unexecuted this long after executable_at lapses and the winner can claim again
+pub const OVERRIDE_GRACE_PERIOD : u64 = 60 * 60 * 24 * 7;
+pub const MAX_REASON_LEN : usize = 64;
+
+pub const WINNER_OVERRIDE_SIZE : usize = 32 + 1 + 32 + 32 + 4 + MAX_REASON_LEN + 8 + 8 + 1 + 1 + 1;
+#[account]
+pub struct WinnerOverride {
+    pub round : Pubkey,
+    pub place : u8,
+    pub previous_winner : Pubkey,
+    pub new_winner : Pubkey,
+    pub reason : String,
+    pub proposed_at : u64,
+    pub executable_at : u64,
+    pub executed : bool,
+    pub cancelled : bool,
+    pub bump : u8,
+}
+
+pub const RANGES_PAGE_LEN : usize = 32;
+
+// tickets bought by one player in one round, PDA [round, player]; a single fetch shows the holdings
+// of any player with up to RANGES_PAGE_LEN ranges, more spill into PlayerRanges pages
+pub const PLAYER_ENTRY_SIZE : usize = 32 + 32 + 4 + 8 + 8 + 1 + 4 + TICKET_RANGE_SIZE * RANGES_PAGE_LEN + 4 + 1;
+#[account]
+pub struct PlayerEntry {
+    pub round : Pubkey,
+    pub player : Pubkey,
+    pub tickets : u32,
+    pub total_spent : u64,
+    pub fees_paid : u64,
+    pub refunded : bool,
+    // the first RANGES_PAGE_LEN ranges, consecutive purchases are merged into one range
+    pub ranges : Vec<TicketRange>,
+    // overflow PlayerRanges pages 0..range_pages
+    pub range_pages : u32,
+    pub bump : u8,
+}
+
+// one overflow page of a player's ticket ranges, PDA [round, player, "ranges", page_index];
+// a new page opens whenever the last one is full, so wallets are not capped by one account's size
+pub const PLAYER_RANGES_SIZE : usize = 32 + 32 + 4 + 4 + TICKET_RANGE_SIZE * RANGES_PAGE_LEN + 1;
+#[account]
+pub struct PlayerRanges {
+    pub round : Pubkey,
+    pub player : Pubkey,
+    pub page_index : u32,
+    pub ranges : Vec<TicketRange>,
+    pub bump : u8,
+}
+
+pub fn holds_ticket(
+    ranges : &[TicketRange],
+    index : u32,
+    ) -> bool {
+    ranges.iter().any(|r| r.start <= index && index < r.end)
+}
+
+// extends the last range when the purchase continues it, otherwise appends one while there is room
+pub fn add_ticket_range(
+    ranges : &mut Vec<TicketRange>,
+    start : u32,
+    quantity : u32,
+    ) -> bool {
+    match ranges.last_mut() {
+        Some(range) if range.end == start => range.end += quantity,
+        _ => {
+            if ranges.len() >= RANGES_PAGE_LEN {
+                return false;
+            }
+            ranges.push(TicketRange {
+                start,
+                end : start + quantity,
+            });
+        }
+    }
+    true
+}
+
+// tickets [start, end)
+pub const TICKET_RANGE_SIZE : usize = 4 + 4;
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy)]
+pub struct TicketRange {
+    pub start : u32,
+    pub end : u32,
+}
+
+// creates the player entry PDA [round, player] and returns its empty state, written back by save_player_entry
+pub fn create_player_entry<'info>(
+    payer : AccountInfo<'info>,
+    entry : AccountInfo<'info>,
+    system_program : AccountInfo<'info>,
+    rent : &Rent,
+    round : &Pubkey,
+    player : &Pubkey,
+    bump : u8,
+    ) -> core::result::Result<PlayerEntry, ProgramError> {
+    let entry_seeds : &[&[u8]] = &[round.as_ref(), player.as_ref(), &[bump]];
+    let entry_address = Pubkey::create_program_address(entry_seeds, &crate::ID)
+        .map_err(|_| PoolError::InvalidPlayerEntry)?;
+    if !cmp_pubkeys(entry.key, &entry_address) {
+        return Err(PoolError::InvalidPlayerEntry.into());
+    }
+
+    create_pda_account(
+        CreatePdaAccountParams {
+            payer,
+            new_account: entry,
+            system_program,
+            lamports: rent.minimum_balance(8 + PLAYER_ENTRY_SIZE),
+            space: (8 + PLAYER_ENTRY_SIZE) as u64,
+            owner: &crate::ID,
+            signer_seeds: entry_seeds,
+        }
+    )?;
+
+    Ok(PlayerEntry {
+        round : *round,
+        player : *player,
+        tickets : 0,
+        total_spent : 0,
+        fees_paid : 0,
+        refunded : false,
+        ranges : vec![],
+        range_pages : 0,
+        bump,
+    })
+}
+
+pub fn load_player_entry(
+    entry : &AccountInfo,
+    round : &Pubkey,
+    player : &Pubkey,
+    ) -> core::result::Result<PlayerEntry, ProgramError> {
+    if !cmp_pubkeys(entry.owner, &crate::ID) {
+        return Err(PoolError::InvalidPlayerEntry.into());
+    }
+
+    let data = entry.try_borrow_data()?;
+    let player_entry = PlayerEntry::try_deserialize(&mut &data[..])?;
+    if player_entry.round != *round || player_entry.player != *player {
+        return Err(PoolError::InvalidPlayerEntry.into());
+    }
+
+    let entry_seeds : &[&[u8]] = &[round.as_ref(), player.as_ref(), &[player_entry.bump]];
+    let entry_address = Pubkey::create_program_address(entry_seeds, &crate::ID)
+        .map_err(|_| PoolError::InvalidPlayerEntry)?;
+    if !cmp_pubkeys(entry.key, &entry_address) {
+        return Err(PoolError::InvalidPlayerEntry.into());
+    }
+
+    Ok(player_entry)
+}
+
+pub fn save_player_entry(
+    entry : &AccountInfo,
+    player_entry : &PlayerEntry,
+    ) -> ProgramResult {
+    let mut data = entry.try_borrow_mut_data()?;
+    let mut writer : &mut [u8] = &mut data;
+    player_entry.try_serialize(&mut writer)?;
+    Ok(())
+}
+
+// creates the ranges page PDA [round, player, "ranges", page_index] and returns its empty state
+#[allow(clippy::too_many_arguments)]
+pub fn create_player_ranges<'info>(
+    payer : AccountInfo<'info>,
+    page : AccountInfo<'info>,
+    system_program : AccountInfo<'info>,
+    rent : &Rent,
+    round : &Pubkey,
+    player : &Pubkey,
+    page_index : u32,
+    bump : u8,
+    ) -> core::result::Result<PlayerRanges, ProgramError> {
+    let page_index_bytes = page_index.to_le_bytes();
+    let page_seeds : &[&[u8]] = &[round.as_ref(), player.as_ref(), b"ranges", &page_index_bytes, &[bump]];
+    let page_address = Pubkey::create_program_address(page_seeds, &crate::ID)
+        .map_err(|_| PoolError::InvalidPlayerEntry)?;
+    if !cmp_pubkeys(page.key, &page_address) {
+        return Err(PoolError::InvalidPlayerEntry.into());
+    }
+
+    create_pda_account(
+        CreatePdaAccountParams {
+            payer,
+            new_account: page,
+            system_program,
+            lamports: rent.minimum_balance(8 + PLAYER_RANGES_SIZE),
+            space: (8 + PLAYER_RANGES_SIZE) as u64,
+            owner: &crate::ID,
+            signer_seeds: page_seeds,
+        }
+    )?;
+
+    Ok(PlayerRanges {
+        round : *round,
+        player : *player,
+        page_index,
+        ranges : vec![],
+        bump,
+    })
+}
+
+pub fn load_player_ranges(
+    page : &AccountInfo,
+    round : &Pubkey,
+    player : &Pubkey,
+    ) -> core::result::Result<PlayerRanges, ProgramError> {
+    if !cmp_pubkeys(page.owner, &crate::ID) {
+        return Err(PoolError::InvalidPlayerEntry.into());
+    }
+
+    let data = page.try_borrow_data()?;
+    let player_ranges = PlayerRanges::try_deserialize(&mut &data[..])?;
+    if player_ranges.round != *round || player_ranges.player != *player {
+        return Err(PoolError::InvalidPlayerEntry.into());
+    }
+
+    let page_index_bytes = player_ranges.page_index.to_le_bytes();
+    let page_seeds : &[&[u8]] = &[round.as_ref(), player.as_ref(), b"ranges", &page_index_bytes, &[player_ranges.bump]];
+    let page_address = Pubkey::create_program_address(page_seeds, &crate::ID)
+        .map_err(|_| PoolError::InvalidPlayerEntry)?;
+    if !cmp_pubkeys(page.key, &page_address) {
+        return Err(PoolError::InvalidPlayerEntry.into());
+    }
+
+    Ok(player_ranges)
+}
+
+pub fn save_player_ranges(
+    page : &AccountInfo,
+    player_ranges : &PlayerRanges,
+    ) -> ProgramResult {
+    let mut data = page.try_borrow_mut_data()?;
+    let mut writer : &mut [u8] = &mut data;
+    player_ranges.try_serialize(&mut writer)?;
+    Ok(())
+}
+
+// affiliate registered with a pool, PDA [pool, "referrer", owner], holds its unclaimed lamport commission;
+// token commissions accrue in its PDA [referrer, "commission", mint] token accounts
+pub const REFERRER_SIZE : usize = 32 + 32 + 8 + 8 + 8 + 1;
+#[account]
+pub struct Referrer {
+    pub pool : Pubkey,
+    pub owner : Pubkey,
+    // ticket revenue brought in, fees excluded
+    pub referred_volume : u64,
+    pub earned : u64,
+    pub claimed : u64,
+    pub bump : u8,
+}
+
+// only init in register_referrer creates program-owned Referrer accounts, so owner and discriminator suffice
+pub fn load_referrer(
+    a : &AccountInfo,
+    pool : &Pubkey,
+    buyer : &Pubkey,
+    ) -> core::result::Result<Referrer, ProgramError> {
+    if !cmp_pubkeys(a.owner, &crate::ID) {
+        return Err(PoolError::InvalidReferrer.into());
+    }
+
+    let data = a.try_borrow_data()?;
+    let referrer = Referrer::try_deserialize(&mut &data[..])?;
+    if referrer.pool != *pool || referrer.owner == *buyer {
+        return Err(PoolError::InvalidReferrer.into());
+    }
+
+    Ok(referrer)
+}
+
+pub fn save_referrer(
+    a : &AccountInfo,
+    referrer : &Referrer,
+    ) -> ProgramResult {
+    let mut data = a.try_borrow_mut_data()?;
+    let mut writer : &mut [u8] = &mut data;
+    referrer.try_serialize(&mut writer)?;
+    Ok(())
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq)]
+pub enum AdminAction {
+    StartRound,
+    FinishRound,
+    Withdraw,
+    Deposit,
+    SetAdmins,
+    SetOracleProgram,
+    RequestRandomness,
+    ProposeOverride,
+    ExecuteOverride,
+    UpdatePoolConfig,
+    SetFeeRecipients,
+    InitRoundVault,
+    CancelRound,
+    CancelOverride,
+    SetReferralShare,
+}
+
+// one privileged call approved by the pool admins, PDA [pool, "proposal", index]
+pub const ADMIN_PROPOSAL_SIZE : usize = 32 + 8 + 1 + 32 + 8 + 4 + 32 * MAX_ADMINS + 1 + 1;
+#[account]
+pub struct AdminProposal {
+    pub pool : Pubkey,
+    pub index : u64,
+    pub action : AdminAction,
+    // round the action applies to, or get_admins_key of the new set for SetAdmins
+    pub target : Pubkey,
+    pub amount : u64,
+    pub approvals : Vec<Pubkey>,
+    pub executed : bool,
+    pub bump : u8,
+}
+
+pub fn check_admins(
+    admins : &[Pubkey],
+    threshold : u8,
+    ) -> ProgramResult {
+    if admins.len() > MAX_ADMINS || threshold as usize > admins.len() {
+        return Err(PoolError::InvalidAdmin.into());
+    }
+
+    for (i, admin) in admins.iter().enumerate() {
+        if admins[..i].contains(admin) {
+            return Err(PoolError::InvalidAdmin.into());
+        }
+    }
+    Ok(())
+}
+
+// a SetAdmins proposal targets this digest of the new admin set
+pub fn get_admins_key(
+    admins : &[Pubkey],
+    threshold : u8,
+    ) -> Pubkey {
+    let mut data : Vec<&[u8]> = admins.iter().map(|a| a.as_ref()).collect();
+    let threshold_bytes = [threshold];
+    data.push(&threshold_bytes);
+    get_args_key(&data)
+}
+
+// proposals for calls taking several arguments target this digest of them, in call order
+pub fn get_args_key(
+    args : &[&[u8]],
+    ) -> Pubkey {
+    Pubkey::new_from_array(hashv(args).to_bytes())
+}
+
+// with an admin set, a privileged call must match an approved proposal, which is then spent;
+// every privileged call takes the proposal as its admin_proposal account, ignored while the pool has no admin set
+pub fn consume_admin_approval(
+    pool : &ProgramAccount<Pool>,
+    proposal_info : &AccountInfo,
+    action : AdminAction,
+    target : &Pubkey,
+    amount : u64,
+    ) -> ProgramResult {
+    if pool.threshold == 0 {
+        return Ok(());
+    }
+
+    if !cmp_pubkeys(proposal_info.owner, &crate::ID) {
+        return Err(PoolError::InvalidAdminProposal.into());
+    }
+
+    let mut proposal = {
+        let data = proposal_info.try_borrow_data()?;
+        AdminProposal::try_deserialize(&mut &data[..])?
+    };
+
+    if proposal.pool != pool.key()
+        || proposal.executed
+        || proposal.action != action
+        || proposal.target != *target
+        || proposal.amount != amount
+    {
+        return Err(PoolError::InvalidAdminProposal.into());
+    }
+
+    // approvals from admins removed since the proposal was made do not count
+    let approvals = proposal.approvals.iter().filter(|a| pool.admins.contains(a)).count();
+    if approvals < pool.threshold as usize {
+        return Err(PoolError::MultisigApprovalMissing.into());
+    }
+
+    proposal.executed = true;
+    let mut data = proposal_info.try_borrow_mut_data()?;
+    let mut writer : &mut [u8] = &mut data;
+    proposal.try_serialize(&mut writer)?;
+    Ok(())
+}
+
+pub const CLAIM_RECEIPT_SIZE : usize = 32 + 32 + 1 + 4 + 8 + 8 + 1;
+#[account]
+pub struct ClaimReceipt {
+    pub round : Pubkey,
+    pub claimer : Pubkey,
+    pub place : u8,
+    pub ticket_index : u32,
+    pub amount : u64,
+    pub claimed_at : u64,
+    pub bump : u8,
+}
+
+// creates the receipt PDA [round, "receipt", place] and writes it
+pub fn create_claim_receipt<'info>(
+    payer : AccountInfo<'info>,
+    receipt : AccountInfo<'info>,
+    system_program : AccountInfo<'info>,
+    rent : &Rent,
+    claim_receipt : &ClaimReceipt,
+    ) -> ProgramResult {
+    let receipt_seeds : &[&[u8]] = &[
+        claim_receipt.round.as_ref(),
+        b"receipt",
+        &[claim_receipt.place],
+        &[claim_receipt.bump],
+    ];
+    let receipt_address = Pubkey::create_program_address(receipt_seeds, &crate::ID)
+        .map_err(|_| PoolError::InvalidClaimReceipt)?;
+    if !cmp_pubkeys(receipt.key, &receipt_address) {
+        return Err(PoolError::InvalidClaimReceipt.into());
+    }
+
+    create_pda_account(
+        CreatePdaAccountParams {
+            payer,
+            new_account: receipt.clone(),
+            system_program,
+            lamports: rent.minimum_balance(8 + CLAIM_RECEIPT_SIZE),
+            space: (8 + CLAIM_RECEIPT_SIZE) as u64,
+            owner: &crate::ID,
+            signer_seeds: receipt_seeds,
+        }
+    )?;
+
+    let mut data = receipt.try_borrow_mut_data()?;
+    let mut writer : &mut [u8] = &mut data;
+    claim_receipt.try_serialize(&mut writer)?;
+    Ok(())
+}
+
+// a page stays under the 10 KiB an account created through CPI may allocate
+pub const LEDGER_PAGE_LEN : usize = 280;
+pub const LEDGER_HEADER_SIZE : usize = 8 + 32 + 4 + 4 + 4;
+pub const LEDGER_PAGE_SIZE : usize = LEDGER_HEADER_SIZE + TICKET_DATA_SIZE * LEDGER_PAGE_LEN;
+// header only, the TicketData entries follow it in the same account
+#[account(zero_copy)]
+#[repr(C)]
+pub struct TicketList {
+    pub round : Pubkey,
+    pub page_index : u32,
+    // entries written to this page
+    pub entries : u32,
+    // round-wide index of the first ticket recorded on this page
+    pub first_ticket : u32,
+}
+
+// one entry per run of consecutive tickets held by the same owner, ending before cumulative_end
+pub const TICKET_DATA_SIZE : usize = 32 + 4;
+#[zero_copy]
+#[repr(C)]
+pub struct TicketData{
+    pub owner : Pubkey,
+    pub cumulative_end : u32,
+}
+
+// #[zero_copy] only makes the struct packed, the ledger casts raw page bytes into entries
+unsafe impl bytemuck::Zeroable for TicketData {}
+unsafe impl bytemuck::Pod for TicketData {}
+
+// creates the ledger page PDA [round, "ledger", page_index] and writes its header
+#[allow(clippy::too_many_arguments)]
+pub fn create_ledger_page<'info>(
+    payer : AccountInfo<'info>,
+    page : AccountInfo<'info>,
+    system_program : AccountInfo<'info>,
+    rent : &Rent,
+    round : &Pubkey,
+    page_index : u32,
+    first_ticket : u32,
+    bump : u8,
+    ) -> ProgramResult {
+    let page_index_bytes = page_index.to_le_bytes();
+    let page_seeds : &[&[u8]] = &[round.as_ref(), b"ledger", &page_index_bytes, &[bump]];
+    let page_address = Pubkey::create_program_address(page_seeds, &crate::ID)
+        .map_err(|_| PoolError::InvalidPoolLedger)?;
+    if !cmp_pubkeys(page.key, &page_address) {
+        return Err(PoolError::InvalidPoolLedger.into());
+    }
+
+    create_pda_account(
+        CreatePdaAccountParams {
+            payer,
+            new_account: page.clone(),
+            system_program,
+            lamports: rent.minimum_balance(LEDGER_PAGE_SIZE),
+            space: LEDGER_PAGE_SIZE as u64,
+            owner: &crate::ID,
+            signer_seeds: page_seeds,
+        }
+    )?;
+
+    let mut data = page.data.borrow_mut();
+    data[..8].copy_from_slice(&TicketList::discriminator());
+
+    let ticket_list : &mut TicketList = bytemuck::from_bytes_mut(&mut data[8..LEDGER_HEADER_SIZE]);
+    ticket_list.round = *round;
+    ticket_list.page_index = page_index;
+    ticket_list.entries = 0;
+    ticket_list.first_ticket = first_ticket;
+
+    Ok(())
+}
+
+// creates the program-owned, data-less PDA [round, "escrow"] holding a lamport round's prize money
+pub fn create_round_escrow<'info>(
+    payer : AccountInfo<'info>,
+    escrow : AccountInfo<'info>,
+    system_program : AccountInfo<'info>,
+    rent : &Rent,
+    round : &Pubkey,
+    bump : u8,
+    ) -> ProgramResult {
+    let escrow_seeds : &[&[u8]] = &[round.as_ref(), b"escrow", &[bump]];
+    let escrow_address = Pubkey::create_program_address(escrow_seeds, &crate::ID)
+        .map_err(|_| PoolError::InvalidRoundVault)?;
+    if !cmp_pubkeys(escrow.key, &escrow_address) {
+        return Err(PoolError::InvalidRoundVault.into());
+    }
+
+    create_pda_account(
+        CreatePdaAccountParams {
+            payer,
+            new_account: escrow,
+            system_program,
+            lamports: rent.minimum_balance(0),
+            space: 0,
+            owner: &crate::ID,
+            signer_seeds: escrow_seeds,
+        }
+    )
+}
+
+// Loader::load maps the whole account onto the header, so pages read the header through these
+pub fn load_ticket_list(
+    data : &[u8],
+    ) -> core::result::Result<&TicketList, ProgramError> {
+    let header = data.get(8..LEDGER_HEADER_SIZE).ok_or(PoolError::InvalidPoolLedger)?;
+    Ok(bytemuck::from_bytes(header))
+}
+
+pub fn load_ticket_list_mut(
+    data : &mut [u8],
+    ) -> core::result::Result<&mut TicketList, ProgramError> {
+    let header = data.get_mut(8..LEDGER_HEADER_SIZE).ok_or(PoolError::InvalidPoolLedger)?;
+    Ok(bytemuck::from_bytes_mut(header))
+}
+
+// the loader already checks owner and discriminator, the header must also point back at the round
+pub fn check_ticket_ledger(
+    a : &Loader<TicketList>,
+    round : &Pubkey,
+    page_index : u32,
+    ) -> ProgramResult {
+    let info = a.to_account_info();
+    let data = info.try_borrow_data()?;
+    if data.len() < LEDGER_PAGE_SIZE {
+        return Err(PoolError::InvalidPoolLedger.into());
+    }
+
+    let ticket_list = load_ticket_list(&data)?;
+    if !cmp_pubkeys(&{ ticket_list.round }, round) || ticket_list.page_index != page_index {
+        return Err(PoolError::InvalidPoolLedger.into());
+    }
+    Ok(())
+}
+
+pub fn set_ticket_owner(
+    a : &Loader<TicketList>,
+    index : usize,
+    ticket_data : TicketData,
+    ) -> ProgramResult {
+    let info = a.to_account_info();
+    let mut arr = info.try_borrow_mut_data()?;
+    let tickets : &mut [TicketData] = bytemuck::try_cast_slice_mut(&mut arr[LEDGER_HEADER_SIZE..])
+        .map_err(|_| PoolError::InvalidPoolLedger)?;
+    let ticket = tickets.get_mut(index).ok_or(PoolError::InvalidPoolLedger)?;
+    *ticket = ticket_data;
+    Ok(())
+}
+
+pub fn set_entries(
+    a : &Loader<TicketList>,
+    entries : u32,
+    ) -> ProgramResult {
+    if entries as usize > LEDGER_PAGE_LEN {
+        return Err(PoolError::TicketLimitReached.into());
+    }
+    let info = a.to_account_info();
+    let mut data = info.try_borrow_mut_data()?;
+    load_ticket_list_mut(&mut data)?.entries = entries;
+    Ok(())
+}
+
+// finds the run covering a round-wide ticket index among the given ledger pages
+pub fn get_winning_ticket(
+    pages : &[AccountInfo],
+    round : &Pubkey,
+    index : usize,
+    ) -> core::result::Result<TicketData, ProgramError> {
+    let index = index as u32;
+    for page in pages {
+        if Loader::<TicketList>::try_from(&crate::ID, page).is_err() {
+            continue;
+        }
+
+        let arr = page.try_borrow_data()?;
+        let (entries, first_ticket) = {
+            let ticket_list = load_ticket_list(&arr)?;
+            if ticket_list.round != *round {
+                continue;
+            }
+            (ticket_list.entries as usize, ticket_list.first_ticket)
+        };
+
+        let tickets : &[TicketData] = bytemuck::try_cast_slice(&arr[LEDGER_HEADER_SIZE..])
+            .map_err(|_| PoolError::InvalidPoolLedger)?;
+        let tickets = tickets.get(..entries).ok_or(PoolError::InvalidPoolLedger)?;
+
+        let last = match tickets.last() {
+            Some(last) => last,
+            None => continue,
+        };
+        if index < first_ticket || index >= last.cumulative_end {
+            continue;
+        }
+
+        // cumulative_end is strictly increasing, so the first run ending past index holds it
+        let position = tickets.partition_point(|t| t.cumulative_end <= index);
+        return Ok(tickets[position]);
+    }
+
+    Err(PoolError::LedgerPageMissing.into())
 }
 
-#[derive(Accounts)]
-pub struct Withdraw<'info> {
-    #[account(mut)]
-    owner : Signer<'info>,
+pub struct RoundTokenAccounts<'info> {
+    pub token_account : AccountInfo<'info>,
+    pub vault : AccountInfo<'info>,
+    pub token_program : AccountInfo<'info>,
+}
 
-    #[account(mut,
-        has_one = owner)]
-    pool : ProgramAccount<'info, Pool>,
+// a referrer's PDA [referrer, "commission", mint] token account, held by the pool
+pub fn check_commission_account(
+    a : &AccountInfo,
+    referrer : &Pubkey,
+    mint : &Pubkey,
+    pool : &Pubkey,
+    ) -> ProgramResult {
+    let (commission_address, _) = Pubkey::find_program_address(
+        &[referrer.as_ref(), b"commission", mint.as_ref()],
+        &crate::ID,
+    );
+    if !cmp_pubkeys(a.key, &commission_address) {
+        return Err(PoolError::InvalidReferrer.into());
+    }
 
-    #[account(mut,
-        has_one = pool,
-        seeds = [round.pool.key().as_ref(), round.round_name.as_ref()], 
-        bump = round.bump)]
-    round : ProgramAccount<'info, Round>
+    let commission = check_token_account(a, mint)?;
+    if !cmp_pubkeys(&commission.owner, pool) {
+        return Err(PoolError::InvalidReferrer.into());
+    }
+    Ok(())
 }
 
-#[derive(Accounts)]
-pub struct Deposit<'info> {
-    #[account(mut)]
-    owner : Signer<'info>,
+// token rounds pass [user token, round vault, token program] as the leading remaining accounts
+pub fn get_round_token_accounts<'info>(
+    round : &Round,
+    accounts : &[AccountInfo<'info>],
+    ) -> core::result::Result<RoundTokenAccounts<'info>, ProgramError> {
+    if accounts.len() < 3 {
+        return Err(PoolError::InvalidTokenAccount.into());
+    }
 
-    #[account(mut,
-        has_one = owner)]
-    pool : ProgramAccount<'info, Pool>,
+    let token_account = accounts[0].clone();
+    let vault = accounts[1].clone();
+    let token_program = accounts[2].clone();
 
-    #[account(mut,
-        has_one = pool,
-        seeds = [round.pool.key().as_ref(), round.round_name.as_ref()], 
-        bump = round.bump)]
-    round : ProgramAccount<'info, Round>,
+    if !cmp_pubkeys(vault.key, &round.vault) {
+        return Err(PoolError::InvalidRoundVault.into());
+    }
+    if !cmp_pubkeys(token_program.key, &spl_token::id()) {
+        return Err(PoolError::InvalidTokenProgram.into());
+    }
+    check_token_account(&token_account, &round.mint)?;
 
-    system_program : Program<'info, System>,
+    Ok(RoundTokenAccounts {
+        token_account,
+        vault,
+        token_program,
+    })
 }
 
-pub const POOL_SIZE : usize = 32 + 32 + 32 + 32 + 8 + 1;
-#[account]
-pub struct Pool {
-    pub owner : Pubkey,
-    pub rand : Pubkey,
-    pub fee_receiver : Pubkey,
-    pub winner : Pubkey,
-    pub fee : u64,
-    pub bump : u8,
+pub fn check_token_account(
+    a : &AccountInfo,
+    mint : &Pubkey,
+    ) -> core::result::Result<spl_token::state::Account, ProgramError> {
+    if !cmp_pubkeys(a.owner, &spl_token::id()) {
+        return Err(PoolError::InvalidTokenAccount.into());
+    }
+    let token_account = spl_token::state::Account::unpack(&a.data.borrow())?;
+    if !cmp_pubkeys(&token_account.mint, mint) {
+        return Err(PoolError::InvalidTokenMint.into());
+    }
+    Ok(token_account)
 }
 
-pub const ROUND_SIZE : usize = 32 + 32 + 32 + 8 + 8 + 8 + 8 + 1 + 1 + 4 + 10 + 1;
-#[account]
-pub struct Round {
-    pub pool : Pubkey,
-    pub ticket_ledger : Pubkey,
-    pub winner : Pubkey,
-    pub total_ticket : u64,
-    pub start_time : u64,
-    pub round_period : u64,
-    pub tvl : u64,
-    pub claimed : bool,
-    pub finished : bool,
-    pub round_name : String,
-    pub bump : u8
+// layout the oracle program writes after its 8 byte discriminator
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy)]
+pub struct OracleRandomness {
+    pub requester : Pubkey,
+    pub randomness : [u8; 32],
 }
 
-pub const MAX_LEN : usize = 10000;
-pub const POOL_LEDGER_SIZE : usize = 32 + 4 + TICKET_DATA_SIZE * MAX_LEN;
-#[account]
-#[derive(Default)]
-pub struct TicketList {
-    pub round : Pubkey,
-    pub last_number : u32,
-    pub ticket_ledger : Vec<TicketData>
+pub fn load_oracle_randomness(
+    a : &AccountInfo,
+    oracle_program : &Pubkey,
+    ) -> core::result::Result<OracleRandomness, ProgramError> {
+    if !cmp_pubkeys(a.owner, oracle_program) {
+        return Err(PoolError::InvalidOracle.into());
+    }
+
+    let arr = a.data.borrow();
+    if arr.len() < 8 + 32 + 32 {
+        return Err(PoolError::InvalidOracle.into());
+    }
+    Ok(OracleRandomness::try_from_slice(&arr[8..8 + 32 + 32])?)
 }
 
-pub const TICKET_DATA_SIZE : usize = 8 + 32;
-#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy)]
-pub struct TicketData{
-    pub ticket_index : u64,
-    pub owner : Pubkey,
+// the round's oracle account, passed as the first remaining account, still holds no randomness
+pub fn is_oracle_unanswered(
+    round : &Round,
+    oracle_program : &Pubkey,
+    oracle : Option<&AccountInfo>,
+    ) -> core::result::Result<bool, ProgramError> {
+    let oracle = oracle.ok_or(PoolError::InvalidOracle)?;
+    if !cmp_pubkeys(oracle.key, &round.oracle) {
+        return Err(PoolError::InvalidOracle.into());
+    }
+
+    Ok(load_oracle_randomness(oracle, oracle_program)?.randomness == [0; 32])
 }
 
-pub fn set_ticket_owner(
-    a: &mut AccountInfo,
-    index : usize,
-    ticket_data : TicketData,
-    ){
-    let mut arr = a.data.borrow_mut();
-    let data_array = ticket_data.try_to_vec().unwrap();
-    let vec_start = 8 + 32 + 4 + TICKET_DATA_SIZE * index;
-    for i in 0..data_array.len(){
-        arr[vec_start+i] = data_array[i];
+pub fn get_oracle_randomness(
+    a : &AccountInfo,
+    oracle_program : &Pubkey,
+    requester : &Pubkey,
+    ) -> core::result::Result<[u8; 32], ProgramError> {
+    let result = load_oracle_randomness(a, oracle_program)?;
+
+    if !cmp_pubkeys(&result.requester, requester) {
+        return Err(PoolError::InvalidOracle.into());
+    }
+    if result.randomness == [0; 32] {
+        return Err(PoolError::RandomnessNotFulfilled.into());
     }
+    Ok(result.randomness)
 }
 
-pub fn set_last_number(
-    a: &mut AccountInfo,
-    number : u32,
-    ){
-    let mut arr = a.data.borrow_mut();
-    let data_array = number.try_to_vec().unwrap();
-    let vec_start = 40;
-    for i in 0..data_array.len() {
-        arr[vec_start+i] = data_array[i];
-    }    
+pub fn get_round_end_time(
+    round : &Round,
+    ) -> u64 {
+    round.start_time + round.round_period
 }
 
-pub fn get_winning_ticket(
-    a : &AccountInfo,
-    index : usize,
-    ) -> core::result::Result<TicketData, ProgramError> {
-    let arr = a.data.borrow();
-    let vec_start = 8 + 32 + 4 + TICKET_DATA_SIZE * index;
-    let data_array = &arr[vec_start..vec_start+TICKET_DATA_SIZE];
-    let ticket_data : TicketData = TicketData::try_from_slice(data_array)?;
-    Ok(ticket_data)
+// what the owner may withdraw: nothing while players may still be owed prizes or refunds
+pub fn get_round_surplus(
+    round : &Round,
+    ) -> core::result::Result<u64, ProgramError> {
+    if round.cancelled {
+        let outstanding = round.ticket_sales.saturating_sub(round.refunded_amount);
+        return Ok(round.tvl.saturating_sub(outstanding));
+    }
+
+    if !round.finished {
+        return Err(PoolError::RoundNotFinished.into());
+    }
+
+    if !round.claimed {
+        return Err(PoolError::PrizesUnclaimed.into());
+    }
+
+    Ok(round.tvl)
+}
+
+// sales close once the period has elapsed or every ticket is sold
+pub fn is_sales_closed(
+    round : &Round,
+    now : u64,
+    ) -> bool {
+    now >= get_round_end_time(round) || round.last_number as u64 >= round.total_ticket
+}
+
+// hash of round.draw_slot from SlotHashes; once it has aged out the draw is never rescheduled,
+// since the outcome was public from draw_slot on, and the round can only be cancelled
+pub fn get_draw_slot_hash(
+    round : &Round,
+    slot_hashes : &AccountInfo,
+    clock : &Clock,
+    ) -> core::result::Result<[u8; 32], ProgramError> {
+    if !cmp_pubkeys(slot_hashes.key, &slot_hashes::id()) {
+        return Err(PoolError::IncorrectSlotHashesPubkey.into());
+    }
+
+    if clock.slot <= round.draw_slot {
+        return Err(PoolError::InvalidTime.into());
+    }
+
+    // bincode Vec<(Slot, Hash)>, newest first
+    let data = slot_hashes.try_borrow_data()?;
+    let len = u64::from_le_bytes(*array_ref![data, 0, 8]) as usize;
+    for i in 0..len.min(SLOT_HASHES_LEN) {
+        let offset = 8 + i * 40;
+        if data.len() < offset + 40 {
+            break;
+        }
+        let slot = u64::from_le_bytes(*array_ref![data, offset, 8]);
+        if slot == round.draw_slot {
+            return Ok(*array_ref![data, offset + 8, 32]);
+        }
+        if slot < round.draw_slot {
+            break;
+        }
+    }
+
+    Err(PoolError::DrawSlotExpired.into())
+}
+
+pub fn is_draw_expired(
+    round : &Round,
+    slot : u64,
+    ) -> bool {
+    round.revealed && slot > round.draw_slot + SLOT_HASHES_LEN as u64
+}
+
+// finish_round and crank_finish_round share this seed, so both paths always draw the same winners;
+// the owner's committed seed is mixed in so neither side can pick the outcome alone
+pub fn get_draw_seed(
+    round : &Round,
+    draw_slot_hash : &[u8; 32],
+    ) -> [u8; 32] {
+    hashv(&[&round.commitment, &round.reveal, draw_slot_hash]).to_bytes()
+}
+
+pub fn check_fee_recipients(
+    recipients : &[FeeRecipient],
+    ) -> ProgramResult {
+    if recipients.is_empty() || recipients.len() > MAX_FEE_RECIPIENTS {
+        return Err(PoolError::InvalidFeeSchedule.into());
+    }
+
+    let mut total_share = 0;
+    for fee_recipient in recipients {
+        if fee_recipient.share == 0 {
+            return Err(PoolError::InvalidFeeSchedule.into());
+        }
+        total_share += fee_recipient.share as u64;
+    }
+
+    if total_share != FEE_DENOMINATOR {
+        return Err(PoolError::InvalidFeeSchedule.into());
+    }
+    Ok(())
+}
+
+pub fn check_prize_tiers(
+    tiers : &[PrizeTier],
+    ) -> ProgramResult {
+    if tiers.is_empty() || tiers.len() > MAX_TIERS {
+        return Err(PoolError::InvalidRanking.into());
+    }
+
+    let mut winners = 0;
+    let mut total_share = 0;
+    for tier in tiers {
+        if tier.winners == 0 || tier.share == 0 {
+            return Err(PoolError::InvalidRanking.into());
+        }
+        winners += tier.winners as usize;
+        total_share += tier.winners as u64 * tier.share as u64;
+    }
+
+    if winners > MAX_WINNERS || total_share != FEE_DENOMINATOR {
+        return Err(PoolError::InvalidRanking.into());
+    }
+    Ok(())
+}
+
+// draws a distinct ticket for every place; places beyond the tickets sold stay undrawn
+// and their shares go to the drawn places
+pub fn draw_winners(
+    round : &mut Round,
+    round_key : &Pubkey,
+    pages : &[AccountInfo],
+    seed : &[u8; 32],
+    ) -> ProgramResult {
+    if round.cancelled {
+        return Err(PoolError::RoundCancelled.into());
+    }
+
+    let sold = round.last_number;
+    if sold == 0 {
+        return Err(PoolError::NoTicketsSold.into());
+    }
+
+    if sold < round.min_tickets {
+        return Err(PoolError::MinTicketsNotReached.into());
+    }
+
+    let prize_pot = round.tvl;
+    let mut winners : Vec<Winner> = vec![];
+    let mut shares : Vec<u64> = vec![];
+    let mut seed = *seed;
+    for tier in round.tiers.iter() {
+        for _ in 0..tier.winners {
+            if winners.len() as u32 >= sold {
+                break;
+            }
+
+            let mut winner_index;
+            loop {
+                seed = hashv(&[&seed]).to_bytes();
+                winner_index = get_winner_index(&seed, sold)?;
+                if !winners.iter().any(|w| w.ticket_index as usize == winner_index) {
+                    break;
+                }
+            }
+
+            let winner_ticket = get_winning_ticket(pages, round_key, winner_index)?;
+            winners.push(Winner {
+                ticket_index : winner_index as u32,
+                owner : winner_ticket.owner,
+                amount : 0,
+                claimed : false,
+                disputed : false,
+                overridden : false,
+            });
+            shares.push(tier.share as u64);
+        }
+    }
+
+    // with fewer tickets sold than places, the undrawn shares are spread over the drawn places
+    // in proportion to their own, and the rounding dust goes to first place, so the whole pot is paid out
+    let drawn_share : u64 = shares.iter().sum();
+    let mut paid = 0;
+    for (winner, share) in winners.iter_mut().zip(shares.iter()) {
+        winner.amount = (prize_pot as u128 * *share as u128 / drawn_share as u128) as u64;
+        paid += winner.amount;
+    }
+    winners[0].amount += prize_pot - paid;
+
+    round.winners = winners;
+    round.finished = true;
+
+    Ok(())
+}
+
+// maps a random seed onto [0, sold) by rejection sampling so every ticket is equally likely
+pub fn get_winner_index(
+    seed : &[u8; 32],
+    sold : u32,
+    ) -> core::result::Result<usize, ProgramError> {
+    if sold == 0 {
+        return Err(PoolError::NoTicketsSold.into());
+    }
+
+    let sold = sold as u64;
+    let zone = u64::MAX - (u64::MAX % sold);
+    let mut seed = *seed;
+    loop {
+        for i in 0..4 {
+            let value = u64::from_le_bytes(*array_ref![seed, i * 8, 8]);
+            if value < zone {
+                return Ok((value % sold) as usize);
+            }
+        }
+        seed = hashv(&[&seed]).to_bytes();
+    }
+}
+
+pub fn get_commitment(
+    rand : &Pubkey,
+    round : &Pubkey,
+    reveal : &[u8; 32],
+    ) -> [u8; 32] {
+    hashv(&[rand.as_ref(), round.as_ref(), reveal]).to_bytes()
 }
 
-pub fn get_last_number(
-    a : &AccountInfo
-    ) -> core::result::Result<u32, ProgramError>{
-    let arr= a.data.borrow();
-    let data_array = &arr[40..44];
-    let last_number : u32 = u32::try_from_slice(data_array)?;
-    Ok(last_number)
+pub fn get_bps_amount(
+    amount : u64,
+    bps : u64,
+    ) -> u64 {
+    (amount as u128 * bps as u128 / FEE_DENOMINATOR as u128) as u64
 }
 
 #[error]
@@ -505,4 +3429,357 @@ pub enum PoolError {
 
     #[msg("Incorrect collection NFT authority")]
     IncorrectSlotHashesPubkey,
-}
\ No newline at end of file
+
+    #[msg("Invalid fee")]
+    InvalidFee,
+
+    #[msg("Invalid ticket price")]
+    InvalidTicketPrice,
+
+    #[msg("Invalid round vault")]
+    InvalidRoundVault,
+
+    #[msg("Invalid token account")]
+    InvalidTokenAccount,
+
+    #[msg("Invalid token program")]
+    InvalidTokenProgram,
+
+    #[msg("Round seed is not revealed yet")]
+    RevealMissing,
+
+    #[msg("Revealed seed does not match the commitment")]
+    RevealMismatch,
+
+    #[msg("Round seed is already revealed")]
+    AlreadyRevealed,
+
+    #[msg("Ticket sales are closed")]
+    SalesClosed,
+
+    #[msg("Invalid randomness oracle")]
+    InvalidOracle,
+
+    #[msg("Randomness is already requested")]
+    RandomnessAlreadyRequested,
+
+    #[msg("Randomness is not fulfilled yet")]
+    RandomnessNotFulfilled,
+
+    #[msg("Round is already finished")]
+    RoundAlreadyFinished,
+
+    #[msg("No tickets were sold in this round")]
+    NoTicketsSold,
+
+    #[msg("Invalid crank reward")]
+    InvalidCrankReward,
+
+    #[msg("Prize is already claimed")]
+    PrizeAlreadyClaimed,
+
+    #[msg("Prize is under dispute")]
+    PrizeDisputed,
+
+    #[msg("Invalid winner override")]
+    InvalidOverride,
+
+    #[msg("Invalid ticket count")]
+    InvalidTicketCount,
+
+    #[msg("Account creation failed")]
+    AccountCreationFailed,
+
+    #[msg("Ledger page holding the ticket is missing")]
+    LedgerPageMissing,
+
+    #[msg("Wallet ticket limit reached")]
+    WalletTicketLimitReached,
+
+    #[msg("Invalid player entry")]
+    InvalidPlayerEntry,
+
+    #[msg("Player ranges page is full, pass the next page")]
+    TicketRangesPageFull,
+
+    #[msg("Round fees were already collected")]
+    FeesAlreadyCollected,
+
+    #[msg("Round is cancelled")]
+    RoundCancelled,
+
+    #[msg("Round is not cancelled")]
+    RoundNotCancelled,
+
+    #[msg("Minimum ticket count not reached")]
+    MinTicketsNotReached,
+
+    #[msg("Tickets are already refunded")]
+    AlreadyRefunded,
+
+    #[msg("Insufficient round funds")]
+    InsufficientRoundFunds,
+
+    #[msg("Prizes are not claimed yet")]
+    PrizesUnclaimed,
+
+    #[msg("No fees to collect")]
+    NoFeesToCollect,
+
+    #[msg("Invalid fee schedule")]
+    InvalidFeeSchedule,
+
+    #[msg("Invalid fee recipient")]
+    InvalidFeeRecipient,
+
+    #[msg("Invalid referrer")]
+    InvalidReferrer,
+
+    #[msg("Invalid admin")]
+    InvalidAdmin,
+
+    #[msg("Invalid admin proposal")]
+    InvalidAdminProposal,
+
+    #[msg("Not enough admin approvals")]
+    MultisigApprovalMissing,
+
+    #[msg("Draw slot hash expired, the round can only be cancelled")]
+    DrawSlotExpired,
+
+    #[msg("Invalid claim receipt")]
+    InvalidClaimReceipt,
+
+    #[msg("Token mint to failed")]
+    TokenMintToFailed,
+
+    #[msg("SOL transfer failed")]
+    SOLTransferFailed,
+
+    #[msg("Insufficient funds")]
+    InsufficentFunds,
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn seed_with_words(words : [u64; 4]) -> [u8; 32] {
+        let mut seed = [0; 32];
+        for (i, word) in words.iter().enumerate() {
+            seed[i * 8..i * 8 + 8].copy_from_slice(&word.to_le_bytes());
+        }
+        seed
+    }
+
+    // a ledger page holding the given runs as (owner, cumulative_end)
+    fn ledger_page(round : &Pubkey, page_index : u32, first_ticket : u32, runs : &[(Pubkey, u32)]) -> Vec<u8> {
+        let mut data = vec![0; LEDGER_PAGE_SIZE];
+        data[..8].copy_from_slice(&TicketList::discriminator());
+        {
+            let ticket_list = load_ticket_list_mut(&mut data).unwrap();
+            ticket_list.round = *round;
+            ticket_list.page_index = page_index;
+            ticket_list.entries = runs.len() as u32;
+            ticket_list.first_ticket = first_ticket;
+        }
+        let tickets : &mut [TicketData] = bytemuck::try_cast_slice_mut(&mut data[LEDGER_HEADER_SIZE..]).unwrap();
+        for (ticket, (owner, cumulative_end)) in tickets.iter_mut().zip(runs.iter()) {
+            *ticket = TicketData { owner : *owner, cumulative_end : *cumulative_end };
+        }
+        data
+    }
+
+    fn page_info<'a>(key : &'a Pubkey, lamports : &'a mut u64, data : &'a mut [u8]) -> AccountInfo<'a> {
+        AccountInfo::new(key, false, false, lamports, data, &crate::ID, false, 0)
+    }
+
+    fn test_round(sold : u32, tvl : u64, tiers : Vec<PrizeTier>) -> Round {
+        Round {
+            pool : Pubkey::new_unique(),
+            last_number : sold,
+            ledger_page : 0,
+            page_entries : 0,
+            last_buyer : Pubkey::default(),
+            total_ticket : 100,
+            max_tickets_per_wallet : 0,
+            min_tickets : 0,
+            ticket_price : 1,
+            start_time : 0,
+            round_period : 0,
+            tvl,
+            ticket_sales : tvl,
+            fees_collected : 0,
+            refunded_amount : 0,
+            fees_claimed : 0,
+            claimed : false,
+            finished : false,
+            cancelled : false,
+            refund_fees : false,
+            round_name : String::from("test"),
+            mint : Pubkey::default(),
+            vault : Pubkey::default(),
+            commitment : [0; 32],
+            reveal : [0; 32],
+            revealed : false,
+            draw_slot : 0,
+            oracle : Pubkey::default(),
+            tiers,
+            winners : vec![],
+            bump : 0,
+        }
+    }
+
+    fn tier(winners : u8, share : u16) -> PrizeTier {
+        PrizeTier { winners, share }
+    }
+
+    #[test]
+    fn winner_index_stays_below_sold() {
+        for sold in [1, 2, 3, 7, 1000, u32::MAX] {
+            for i in 0..200u32 {
+                let seed = hashv(&[&i.to_le_bytes()]).to_bytes();
+                assert!(get_winner_index(&seed, sold).unwrap() < sold as usize);
+            }
+        }
+    }
+
+    #[test]
+    fn winner_index_skips_words_outside_the_zone() {
+        // u64::MAX is divisible by 3, so the zone ends at u64::MAX itself and that word is rejected
+        let seed = seed_with_words([u64::MAX, 5, 0, 0]);
+        assert_eq!(get_winner_index(&seed, 3).unwrap(), 2);
+
+        // for sold = 2 the zone ends at u64::MAX - 1
+        let seed = seed_with_words([u64::MAX, u64::MAX, u64::MAX, 9]);
+        assert_eq!(get_winner_index(&seed, 2).unwrap(), 1);
+    }
+
+    #[test]
+    fn winner_index_rehashes_when_every_word_is_rejected() {
+        let seed = seed_with_words([u64::MAX; 4]);
+        assert!(get_winner_index(&seed, 3).unwrap() < 3);
+    }
+
+    #[test]
+    fn winner_index_needs_a_ticket() {
+        assert!(get_winner_index(&[7; 32], 0).is_err());
+    }
+
+    #[test]
+    fn prize_tiers_must_split_the_whole_pot() {
+        assert!(check_prize_tiers(&[tier(1, 10000)]).is_ok());
+        assert!(check_prize_tiers(&[tier(1, 5000), tier(2, 1500), tier(4, 500)]).is_ok());
+
+        assert!(check_prize_tiers(&[]).is_err());
+        assert!(check_prize_tiers(&[tier(1, 5000)]).is_err());
+        assert!(check_prize_tiers(&[tier(1, 5000), tier(1, 6000)]).is_err());
+        assert!(check_prize_tiers(&[tier(0, 5000), tier(1, 10000)]).is_err());
+        assert!(check_prize_tiers(&[tier(1, 0), tier(1, 10000)]).is_err());
+        assert!(check_prize_tiers(&[tier(11, 1000)]).is_err());
+        assert!(check_prize_tiers(&[tier(1, 5000), tier(1, 2000), tier(1, 1000), tier(1, 1000), tier(1, 500), tier(1, 500)]).is_err());
+    }
+
+    #[test]
+    fn draw_pays_every_place_and_the_dust_to_first() {
+        let round_key = Pubkey::new_unique();
+        let owners : Vec<Pubkey> = (0..10).map(|_| Pubkey::new_unique()).collect();
+        let runs : Vec<(Pubkey, u32)> = owners.iter().enumerate().map(|(i, o)| (*o, (i as u32 + 1) * 10)).collect();
+        let mut data = ledger_page(&round_key, 0, 0, &runs);
+        let (key, mut lamports) = (Pubkey::new_unique(), 0);
+        let pages = [page_info(&key, &mut lamports, &mut data)];
+
+        let mut round = test_round(100, 1001, vec![tier(1, 5000), tier(2, 2500)]);
+        draw_winners(&mut round, &round_key, &pages, &[3; 32]).unwrap();
+
+        assert!(round.finished);
+        let amounts : Vec<u64> = round.winners.iter().map(|w| w.amount).collect();
+        assert_eq!(amounts, vec![501, 250, 250]);
+        for winner in round.winners.iter() {
+            assert_eq!(winner.owner, owners[winner.ticket_index as usize / 10]);
+        }
+    }
+
+    #[test]
+    fn draw_spreads_undrawn_shares_when_fewer_tickets_than_places() {
+        let round_key = Pubkey::new_unique();
+        let owners = [Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique()];
+        let mut data = ledger_page(&round_key, 0, 0, &[(owners[0], 1), (owners[1], 2), (owners[2], 3)]);
+        let (key, mut lamports) = (Pubkey::new_unique(), 0);
+        let pages = [page_info(&key, &mut lamports, &mut data)];
+
+        for i in 0..20u8 {
+            let mut round = test_round(3, 1000, vec![tier(1, 5000), tier(4, 1250)]);
+            draw_winners(&mut round, &round_key, &pages, &[i; 32]).unwrap();
+
+            assert_eq!(round.winners.len(), 3);
+            let mut drawn : Vec<u32> = round.winners.iter().map(|w| w.ticket_index).collect();
+            drawn.sort_unstable();
+            assert_eq!(drawn, vec![0, 1, 2]);
+
+            // 7500 bps drawn: 1000 * 5000 / 7500 = 666 and 1000 * 1250 / 7500 = 166, 2 left over
+            let amounts : Vec<u64> = round.winners.iter().map(|w| w.amount).collect();
+            assert_eq!(amounts, vec![668, 166, 166]);
+            for winner in round.winners.iter() {
+                assert_eq!(winner.owner, owners[winner.ticket_index as usize]);
+            }
+        }
+    }
+
+    #[test]
+    fn draw_needs_enough_tickets() {
+        let round_key = Pubkey::new_unique();
+        let mut round = test_round(0, 0, vec![tier(1, 10000)]);
+        assert!(draw_winners(&mut round, &round_key, &[], &[0; 32]).is_err());
+
+        let mut round = test_round(3, 1000, vec![tier(1, 10000)]);
+        round.min_tickets = 4;
+        assert!(draw_winners(&mut round, &round_key, &[], &[0; 32]).is_err());
+        assert!(!round.finished);
+    }
+
+    #[test]
+    fn winning_ticket_lookup_across_pages_and_runs() {
+        let round_key = Pubkey::new_unique();
+        let (a, b, c, d) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        // page 0 holds tickets [0, 12): a run of 5, a single ticket, a run of 6; page 1 starts at 12
+        let mut data0 = ledger_page(&round_key, 0, 0, &[(a, 5), (b, 6), (c, 12)]);
+        let mut data1 = ledger_page(&round_key, 1, 12, &[(d, 13), (a, 40)]);
+        let (key0, key1) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let (mut lamports0, mut lamports1) = (0, 0);
+        let pages = [
+            page_info(&key1, &mut lamports1, &mut data1),
+            page_info(&key0, &mut lamports0, &mut data0),
+        ];
+
+        let owner = |index| get_winning_ticket(&pages, &round_key, index).unwrap().owner;
+        for index in 0..5 {
+            assert_eq!(owner(index), a);
+        }
+        assert_eq!(owner(5), b);
+        for index in 6..12 {
+            assert_eq!(owner(index), c);
+        }
+        assert_eq!(owner(12), d);
+        assert_eq!(owner(13), a);
+        assert_eq!(owner(39), a);
+        assert!(get_winning_ticket(&pages, &round_key, 40).is_err());
+    }
+
+    #[test]
+    fn winning_ticket_skips_pages_of_other_rounds() {
+        let round_key = Pubkey::new_unique();
+        let mut other = ledger_page(&Pubkey::new_unique(), 0, 0, &[(Pubkey::new_unique(), 10)]);
+        let mut empty = ledger_page(&round_key, 1, 10, &[]);
+        let (key0, key1) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let (mut lamports0, mut lamports1) = (0, 0);
+        let pages = [
+            page_info(&key0, &mut lamports0, &mut other),
+            page_info(&key1, &mut lamports1, &mut empty),
+        ];
+
+        assert_eq!(
+            get_winning_ticket(&pages, &round_key, 3).err(),
+            Some(PoolError::LedgerPageMissing.into()),
+        );
+    }
+}
@@ -4,14 +4,13 @@ use {
         prelude::{AccountInfo,},
         solana_program::{
             program::{invoke_signed, invoke},
-            system_instruction:: {transfer},
+            system_instruction:: {transfer, create_account, allocate, assign},
             entrypoint:: {ProgramResult},
+            program_memory::sol_memcmp,
+            pubkey::{Pubkey, PUBKEY_BYTES},
+            sysvar::recent_blockhashes,
         },
     },
-    solana_program::{
-        program_memory::sol_memcmp,
-        pubkey::{Pubkey, PUBKEY_BYTES},
-    },
 };
 
 //##Ans
@@ -20,8 +19,7 @@ use {
 // https://doc.rust-lang.org/rust-by-example/fn/closures.html
 // https://doc.rust-lang.org/std/result/enum.Result.html#method.map_err
 // https://stackoverflow.com/questions/37639276/when-should-inline-be-used-in-rust
-pub const BLOCK_HASHES: Pubkey =
-    solana_program::pubkey!("SysvarRecentB1ockHashes11111111111111111111");
+pub const BLOCK_HASHES: Pubkey = recent_blockhashes::ID;
 
 pub fn cmp_pubkeys(a: &Pubkey, b: &Pubkey) -> bool {
     sol_memcmp(a.as_ref(), b.as_ref(), PUBKEY_BYTES) == 0
@@ -177,6 +175,76 @@ pub fn sol_transfer_without_seed(params: SolTransferParamsWithoutSeed<'_>) -> Pr
     result.map_err(|_| PoolError::SOLTransferFailed.into())
 }
 
+pub struct CreatePdaAccountParams<'a: 'b, 'b> {
+    /// CHECK:
+    pub payer: AccountInfo<'a>,
+    /// CHECK:
+    pub new_account: AccountInfo<'a>,
+    /// CHECK:
+    pub system_program: AccountInfo<'a>,
+    /// CHECK:
+    pub lamports: u64,
+    /// CHECK:
+    pub space: u64,
+    /// CHECK:
+    pub owner: &'b Pubkey,
+    /// CHECK:
+    pub signer_seeds: &'b [&'b [u8]],
+}
+
+#[inline(always)]
+pub fn create_pda_account(params: CreatePdaAccountParams<'_, '_>) -> ProgramResult {
+    let CreatePdaAccountParams {
+        payer,
+        new_account,
+        system_program,
+        lamports,
+        space,
+        owner,
+        signer_seeds,
+    } = params;
+
+    // anyone can send lamports to a derived address before it is created, and the
+    // system program refuses `create_account` on an address that already has a
+    // balance, so a pre-funded address is topped up, allocated and assigned instead
+    if new_account.lamports() == 0 {
+        let result = invoke_signed(
+            &create_account(
+                payer.key,
+                new_account.key,
+                lamports,
+                space,
+                owner,
+            ),
+            &[payer, new_account, system_program],
+            &[signer_seeds],
+        );
+        return result.map_err(|_| PoolError::AccountCreationFailed.into());
+    }
+
+    let shortfall = lamports.saturating_sub(new_account.lamports());
+    if shortfall > 0 {
+        invoke(
+            &transfer(payer.key, new_account.key, shortfall),
+            &[payer, new_account.clone(), system_program.clone()],
+        ).map_err(|_| PoolError::AccountCreationFailed)?;
+    }
+
+    invoke_signed(
+        &allocate(new_account.key, space),
+        &[new_account.clone(), system_program.clone()],
+        &[signer_seeds],
+    ).map_err(|_| PoolError::AccountCreationFailed)?;
+
+    let result = invoke_signed(
+        &assign(new_account.key, owner),
+        &[new_account, system_program],
+        &[signer_seeds],
+    );
+
+    result.map_err(|_| PoolError::AccountCreationFailed.into())
+}
+
 //## Why do we need to create this struct? 
 //## Are not we using regular transfer of the token that works with solana built-in methods?
 pub struct TokenSetAuthorityParams<'a>{
@@ -0,0 +1,206 @@
+const anchor = require("@project-serum/anchor");
+const { assert } = require("chai");
+
+const { BN, web3 } = anchor;
+const { PublicKey, Keypair, SystemProgram, SYSVAR_CLOCK_PUBKEY, SYSVAR_RENT_PUBKEY } = web3;
+
+const u32Seed = (n) => {
+  const buf = Buffer.alloc(4);
+  buf.writeUInt32LE(n);
+  return buf;
+};
+
+describe("oracle randomness", () => {
+  const provider = anchor.Provider.env();
+  anchor.setProvider(provider);
+
+  const program = anchor.workspace.SolanaAnchor;
+  const oracleProgram = anchor.workspace.MockOracle;
+
+  const owner = provider.wallet.publicKey;
+  const buyer = Keypair.generate();
+  const rand = Keypair.generate();
+  const request = Keypair.generate();
+  // ignored while the pool has no admin set
+  const adminProposal = Keypair.generate().publicKey;
+  const roundName = "oracle-round";
+
+  let pool, round, ledger, escrow;
+
+  before(async () => {
+    const airdrop = await provider.connection.requestAirdrop(buyer.publicKey, web3.LAMPORTS_PER_SOL);
+    await provider.connection.confirmTransaction(airdrop);
+
+    let poolBump;
+    [pool, poolBump] = await PublicKey.findProgramAddress([rand.publicKey.toBuffer()], program.programId);
+    await program.rpc.initPool(poolBump, new BN(100), new BN(0), {
+      accounts: {
+        owner,
+        pool,
+        rand: rand.publicKey,
+        feeReceiver: owner,
+        systemProgram: SystemProgram.programId,
+      },
+    });
+
+    await program.rpc.setOracleProgram({
+      accounts: {
+        owner,
+        pool,
+        oracleProgram: oracleProgram.programId,
+        adminProposal,
+      },
+    });
+
+    // a single ticket, so the round closes as soon as it is sold
+    let roundBump, ledgerBump, vaultBump;
+    [round, roundBump] = await PublicKey.findProgramAddress(
+      [pool.toBuffer(), Buffer.from(roundName)], program.programId);
+    [ledger, ledgerBump] = await PublicKey.findProgramAddress(
+      [round.toBuffer(), Buffer.from("ledger"), u32Seed(0)], program.programId);
+    [escrow, vaultBump] = await PublicKey.findProgramAddress(
+      [round.toBuffer(), Buffer.from("escrow")], program.programId);
+    await program.rpc.startRound(
+      roundBump,
+      roundName,
+      new BN(1),
+      new BN(60),
+      new BN(web3.LAMPORTS_PER_SOL / 100),
+      new Array(32).fill(0),
+      [{ winners: 1, share: 10000 }],
+      ledgerBump,
+      0,
+      0,
+      vaultBump,
+      {
+        accounts: {
+          owner,
+          pool,
+          ticketLedger: ledger,
+          vault: escrow,
+          roundData: round,
+          adminProposal,
+          clock: SYSVAR_CLOCK_PUBKEY,
+          rent: SYSVAR_RENT_PUBKEY,
+          systemProgram: SystemProgram.programId,
+        },
+      }
+    );
+
+    const [playerEntry, playerBump] = await PublicKey.findProgramAddress(
+      [round.toBuffer(), buyer.publicKey.toBuffer()], program.programId);
+    const [playerRanges, rangesBump] = await PublicKey.findProgramAddress(
+      [round.toBuffer(), buyer.publicKey.toBuffer(), Buffer.from("ranges"), u32Seed(0)], program.programId);
+    await program.rpc.buyTicket(1, ledgerBump, playerBump, rangesBump, {
+      accounts: {
+        owner: buyer.publicKey,
+        pool,
+        round,
+        vault: escrow,
+        ticketLedger: ledger,
+        playerEntry,
+        playerRanges,
+        clock: SYSVAR_CLOCK_PUBKEY,
+        rent: SYSVAR_RENT_PUBKEY,
+        systemProgram: SystemProgram.programId,
+      },
+      signers: [buyer],
+    });
+  });
+
+  it("refuses a request made for another round", async () => {
+    const otherRequest = Keypair.generate();
+    await oracleProgram.rpc.request(Keypair.generate().publicKey, {
+      accounts: {
+        authority: owner,
+        request: otherRequest.publicKey,
+        systemProgram: SystemProgram.programId,
+      },
+      signers: [otherRequest],
+    });
+
+    try {
+      await program.rpc.requestRandomness({
+        accounts: {
+          owner,
+          pool,
+          round,
+          oracle: otherRequest.publicKey,
+          clock: SYSVAR_CLOCK_PUBKEY,
+          adminProposal,
+        },
+      });
+      assert.fail("bound another round's request");
+    } catch (err) {
+      assert.include(err.toString(), "Invalid randomness oracle");
+    }
+  });
+
+  it("binds an oracle request to the round", async () => {
+    await oracleProgram.rpc.request(round, {
+      accounts: {
+        authority: owner,
+        request: request.publicKey,
+        systemProgram: SystemProgram.programId,
+      },
+      signers: [request],
+    });
+
+    await program.rpc.requestRandomness({
+      accounts: {
+        owner,
+        pool,
+        round,
+        oracle: request.publicKey,
+        clock: SYSVAR_CLOCK_PUBKEY,
+        adminProposal,
+      },
+    });
+
+    const roundData = await program.account.round.fetch(round);
+    assert.ok(roundData.oracle.equals(request.publicKey));
+  });
+
+  it("refuses to draw before the oracle fulfils", async () => {
+    try {
+      await program.rpc.consumeRandomness({
+        accounts: { pool, round, oracle: request.publicKey },
+        remainingAccounts: [{ pubkey: ledger, isWritable: false, isSigner: false }],
+      });
+      assert.fail("consumed unfulfilled randomness");
+    } catch (err) {
+      assert.include(err.toString(), "Randomness is not fulfilled yet");
+    }
+  });
+
+  it("draws the winners from the fulfilled randomness", async () => {
+    await oracleProgram.rpc.fulfil(new Array(32).fill(7), {
+      accounts: {
+        authority: owner,
+        request: request.publicKey,
+      },
+    });
+
+    await program.rpc.consumeRandomness({
+      accounts: { pool, round, oracle: request.publicKey },
+      remainingAccounts: [{ pubkey: ledger, isWritable: false, isSigner: false }],
+    });
+
+    const roundData = await program.account.round.fetch(round);
+    assert.ok(roundData.finished);
+    assert.equal(roundData.winners.length, 1);
+    assert.ok(roundData.winners[0].owner.equals(buyer.publicKey));
+  });
+
+  it("draws only once", async () => {
+    try {
+      await program.rpc.consumeRandomness({
+        accounts: { pool, round, oracle: request.publicKey },
+        remainingAccounts: [{ pubkey: ledger, isWritable: false, isSigner: false }],
+      });
+      assert.fail("consumed randomness twice");
+    } catch (err) {
+      assert.include(err.toString(), "already finished");
+    }
+  });
+});